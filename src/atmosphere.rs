@@ -0,0 +1,140 @@
+use std::f32::consts::PI;
+
+use serde::Deserialize;
+
+/// Planet and atmosphere radii in meters. Must match `ray-marcher.glsl`.
+const EARTH_RADIUS: f32 = 6360e3;
+const ATMOSPHERE_RADIUS: f32 = 6420e3;
+/// Scale heights of the Rayleigh and Mie scattering densities, in meters.
+const RAYLEIGH_HEIGHT: f32 = 8e3;
+const MIE_HEIGHT: f32 = 1.2e3;
+/// Extinction coefficients at sea level, per meter.
+const RAYLEIGH_EXTINCTION: [f32; 3] = [5.8e-6, 13.5e-6, 33.1e-6];
+const MIE_EXTINCTION: f32 = 21e-6 * 1.1;
+
+const TRANSMITTANCE_SAMPLES: u32 = 32;
+
+/// Earth-like atmosphere lit by a sun whose position follows the time of day
+/// at the given latitude. North is +Z and east is +X.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Atmosphere {
+    /// Local solar time, in hours.
+    #[serde(default = "default_time_of_day")]
+    pub time_of_day: f32,
+    /// Latitude of the observer, in degrees.
+    #[serde(default = "default_latitude")]
+    pub latitude: f32,
+    /// Day of the year, 0 being January 1st.
+    #[serde(default = "default_day_of_year")]
+    pub day_of_year: f32,
+    /// Multiplier of the Mie (haze) scattering.
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f32,
+    /// Intensity of the sun as a directional light, before atmospheric
+    /// attenuation.
+    #[serde(default = "default_sun_intensity")]
+    pub sun_intensity: f32,
+    /// Whether the time of day advances while the application runs.
+    #[serde(default)]
+    pub animate: bool,
+    /// Hours advanced per second when animated.
+    #[serde(default = "default_day_speed")]
+    pub day_speed: f32,
+}
+
+/// The sun as seen from the ground, after atmospheric attenuation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sun {
+    /// Unit vector pointing toward the sun.
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Atmosphere {
+    /// Direction toward the sun at the given local solar time, from the
+    /// sun's declination and hour angle.
+    pub fn sun_direction(&self, time_of_day: f32) -> [f32; 3] {
+        let declination = (-23.44f32).to_radians()
+            * (2.0 * PI / 365.0 * (self.day_of_year + 10.0)).cos();
+        let hour_angle = (15.0 * (time_of_day - 12.0)).to_radians();
+        let latitude = self.latitude.to_radians();
+
+        let east = -declination.cos() * hour_angle.sin();
+        let north = latitude.cos() * declination.sin()
+            - latitude.sin() * declination.cos() * hour_angle.cos();
+        let up = latitude.sin() * declination.sin()
+            + latitude.cos() * declination.cos() * hour_angle.cos();
+
+        [east, up, north]
+    }
+
+    /// Fraction of the sunlight reaching the ground along `direction`, zero
+    /// once the sun is below the horizon.
+    pub fn transmittance(&self, direction: [f32; 3]) -> [f32; 3] {
+        let origin_height = EARTH_RADIUS + 1.0;
+        // Distance to the top of the atmosphere from a point at the pole.
+        let b = origin_height * direction[1];
+        let c = origin_height * origin_height - ATMOSPHERE_RADIUS * ATMOSPHERE_RADIUS;
+        let distance = -b + (b * b - c).sqrt();
+
+        // The planet blocks the sun.
+        let ground_c = origin_height * origin_height - EARTH_RADIUS * EARTH_RADIUS;
+        if b < 0.0 && b * b - ground_c >= 0.0 {
+            return [0.0; 3];
+        }
+
+        let segment = distance / TRANSMITTANCE_SAMPLES as f32;
+        let mut rayleigh_depth = 0.0;
+        let mut mie_depth = 0.0;
+        for i in 0..TRANSMITTANCE_SAMPLES {
+            let t = segment * (i as f32 + 0.5);
+            let x = direction[0] * t;
+            let y = origin_height + direction[1] * t;
+            let z = direction[2] * t;
+            let height = (x * x + y * y + z * z).sqrt() - EARTH_RADIUS;
+
+            rayleigh_depth += (-height / RAYLEIGH_HEIGHT).exp() * segment;
+            mie_depth += (-height / MIE_HEIGHT).exp() * segment;
+        }
+
+        RAYLEIGH_EXTINCTION.map(|rayleigh| {
+            (-(rayleigh * rayleigh_depth + MIE_EXTINCTION * self.turbidity * mie_depth)).exp()
+        })
+    }
+
+    pub fn sun(&self, time_of_day: f32) -> Sun {
+        let direction = self.sun_direction(time_of_day);
+
+        Sun {
+            direction,
+            color: self.transmittance(direction),
+            intensity: self.sun_intensity,
+        }
+    }
+}
+
+fn default_time_of_day() -> f32 {
+    10.0
+}
+
+fn default_latitude() -> f32 {
+    45.0
+}
+
+/// The June solstice.
+fn default_day_of_year() -> f32 {
+    172.0
+}
+
+fn default_turbidity() -> f32 {
+    1.0
+}
+
+fn default_sun_intensity() -> f32 {
+    3.0
+}
+
+fn default_day_speed() -> f32 {
+    0.5
+}
@@ -0,0 +1,365 @@
+use std::f32::consts::TAU;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use vulkano::device::Properties;
+
+use crate::camera::Camera;
+use crate::profiling::FrameProfiler;
+use crate::settings::RenderSettings;
+use crate::vulkan::MarchStats;
+
+/// Rate the benchmark's clock advances at, in frames per second. Each frame
+/// steps it by the same amount whatever the actual frame time, so that every
+/// run renders the same images.
+pub const FRAME_RATE: f32 = 60.0;
+/// Duration of one loop of the camera path, in seconds of the benchmark's
+/// clock.
+const PATH_DURATION: f32 = 20.0;
+/// Radius of the circle the camera flies along, in units.
+const PATH_RADIUS: f32 = 1.0;
+/// Largest turn of the camera away from its starting direction, in radians.
+const PATH_YAW: f32 = std::f32::consts::FRAC_PI_4;
+const PATH_PITCH: f32 = 0.2;
+/// Measured frames between two frames of the march statistics pass, which
+/// replays the camera path after the measured frames.
+const MARCH_STATS_STRIDE: u32 = 10;
+
+/// Names of the benchmark options, each taking a value after `=`.
+const OPTIONS: [&str; 4] = ["--warmup", "--frames", "--resolution", "--output"];
+
+/// Options of the `--benchmark` mode, parsed from `--warmup=N`, `--frames=N`,
+/// `--resolution=WxH` and `--output=PATH`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkOptions {
+    /// Frames rendered before measuring, for the caches, clocks and
+    /// auto exposure to settle.
+    pub warmup_frames: u32,
+    /// Frames measured.
+    pub frames: u32,
+    /// Size of the window, in physical pixels.
+    pub resolution: [u32; 2],
+    /// Path of the JSON report.
+    pub output: PathBuf,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        BenchmarkOptions {
+            warmup_frames: 120,
+            frames: 1000,
+            resolution: [1280, 720],
+            output: PathBuf::from("benchmark.json"),
+        }
+    }
+}
+
+impl BenchmarkOptions {
+    /// Parses the benchmark options of the command line, `None` without
+    /// `--benchmark`.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        if !args.iter().any(|arg| arg == "--benchmark") {
+            return Ok(None);
+        }
+
+        let mut options = BenchmarkOptions::default();
+        for arg in args {
+            let Some((name, value)) = arg.split_once('=') else {
+                if OPTIONS.contains(&arg.as_str()) {
+                    return Err(format!("Missing value for {arg}, expected {arg}=VALUE"));
+                }
+                continue;
+            };
+            let invalid = || format!("Invalid value for {name}: {value}");
+            match name {
+                "--warmup" => options.warmup_frames = value.parse().map_err(|_| invalid())?,
+                "--frames" => options.frames = value.parse().map_err(|_| invalid())?,
+                "--resolution" => {
+                    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+                    options.resolution = [
+                        width.parse().map_err(|_| invalid())?,
+                        height.parse().map_err(|_| invalid())?,
+                    ];
+                }
+                "--output" => options.output = PathBuf::from(value),
+                _ => return Err(format!("Unknown option {name}")),
+            }
+        }
+
+        if options.frames == 0 || options.resolution.contains(&0) {
+            return Err(String::from("The benchmark needs at least one frame and pixel"));
+        }
+        Ok(Some(options))
+    }
+
+    /// Frames of the march statistics pass, following the measured frames.
+    pub fn march_stats_frames(&self) -> u32 {
+        self.frames.div_ceil(MARCH_STATS_STRIDE)
+    }
+
+    /// Time of the benchmark's clock at the given frame, in seconds. The
+    /// march statistics pass goes through the measured frames again, keeping
+    /// every `MARCH_STATS_STRIDE`th.
+    pub fn clock_time(&self, frame: u32) -> f32 {
+        let measured_end = self.warmup_frames + self.frames;
+        let frame = match frame.checked_sub(measured_end) {
+            Some(stats_frame) => self.warmup_frames + stats_frame * MARCH_STATS_STRIDE,
+            None => frame,
+        };
+        frame as f32 / FRAME_RATE
+    }
+}
+
+/// Camera of the benchmark at the given time in seconds. It flies a loop
+/// starting and ending at the scene's camera, turning left and right and
+/// looking up and down along the way.
+pub fn camera_path(start: &Camera, time: f32) -> Camera {
+    let angle = TAU * time / PATH_DURATION;
+    let mut camera = *start;
+    camera.translate(PATH_RADIUS * angle.sin(), 0.0, PATH_RADIUS * (1.0 - angle.cos()));
+    camera.rotate(PATH_YAW * angle.sin(), PATH_PITCH * (2.0 * angle).sin());
+    camera
+}
+
+/// Results of a benchmark run, written as JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    /// Scene file, `None` for the default scene.
+    pub scene: Option<PathBuf>,
+    pub backend: String,
+    /// Size of the swapchain images, in pixels.
+    pub resolution: [u32; 2],
+    pub warmup_frames: u32,
+    pub frames: u32,
+    pub device: DeviceInfo,
+    pub settings: SettingsInfo,
+    /// Statistics of every timing of the measured frames, the GPU passes and
+    /// frame, and the CPU stages and frame.
+    pub timings: Vec<TimingReport>,
+    /// Iterations of the primary rays along the camera path.
+    pub march_stats: MarchReport,
+}
+
+impl BenchmarkReport {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Graphics card and driver the benchmark ran on.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub device_type: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// Vulkan version supported by the device.
+    pub api_version: String,
+    /// Driver version, encoded the vendor's own way.
+    pub driver_version: u32,
+    pub driver_name: Option<String>,
+    pub driver_info: Option<String>,
+}
+
+impl DeviceInfo {
+    pub fn new(properties: &Properties) -> Self {
+        DeviceInfo {
+            name: properties.device_name.clone(),
+            device_type: format!("{:?}", properties.device_type),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            api_version: properties.api_version.to_string(),
+            driver_version: properties.driver_version,
+            driver_name: properties.driver_name.clone(),
+            driver_info: properties.driver_info.clone(),
+        }
+    }
+}
+
+/// Render settings affecting the frame time.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsInfo {
+    pub quality: String,
+    pub render_mode: String,
+    pub shading_model: String,
+    pub max_bounces: u32,
+    pub shadows: bool,
+    pub ambient_occlusion: bool,
+    pub volumetrics: bool,
+    pub clouds: bool,
+    pub footprint_lod: bool,
+    pub over_relaxation: bool,
+    pub relaxation_factor: f32,
+    pub cone_prepass: bool,
+    pub render_scale: f32,
+    pub upscale_filter: String,
+    pub taa: bool,
+    pub supersampling_grid_size: u32,
+    pub supersampling_pattern: String,
+    pub supersampling_filter: String,
+    pub denoiser: bool,
+    pub auto_exposure: bool,
+    /// Enabled post-processing effects, in order.
+    pub post_effects: Vec<String>,
+}
+
+impl SettingsInfo {
+    pub fn new(settings: &RenderSettings) -> Self {
+        SettingsInfo {
+            quality: format!("{:?}", settings.quality),
+            render_mode: format!("{:?}", settings.render_mode),
+            shading_model: format!("{:?}", settings.shading_model),
+            max_bounces: settings.max_bounces,
+            shadows: settings.shadows,
+            ambient_occlusion: settings.ambient_occlusion,
+            volumetrics: settings.volumetrics,
+            clouds: settings.clouds,
+            footprint_lod: settings.footprint_lod,
+            over_relaxation: settings.over_relaxation,
+            relaxation_factor: settings.relaxation_factor,
+            cone_prepass: settings.cone_prepass,
+            render_scale: settings.render_scale,
+            upscale_filter: format!("{:?}", settings.upscale_filter),
+            taa: settings.taa,
+            supersampling_grid_size: settings.supersampling.grid_size,
+            supersampling_pattern: format!("{:?}", settings.supersampling.pattern),
+            supersampling_filter: format!("{:?}", settings.supersampling.filter),
+            denoiser: settings.denoiser.enabled,
+            auto_exposure: settings.auto_exposure,
+            post_effects: settings.post
+                .iter()
+                .filter(|pass| pass.enabled)
+                .map(|pass| format!("{:?}", pass.effect))
+                .collect(),
+        }
+    }
+}
+
+/// Statistics of a timing over the measured frames, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingReport {
+    pub name: String,
+    pub samples: usize,
+    pub average: f32,
+    pub min: f32,
+    pub p50: f32,
+    pub p90: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+/// Iterations of the primary rays, counted in a separate pass as the counting
+/// slows the frames down.
+#[derive(Debug, Clone, Serialize)]
+pub struct MarchReport {
+    pub rays: u64,
+    pub average_iterations: f64,
+    /// Average iterations of the same rays with plain sphere tracing.
+    pub average_plain_iterations: f64,
+    /// Fraction of the plain sphere tracing iterations saved.
+    pub savings: f64,
+}
+
+impl MarchReport {
+    pub fn new(stats: &MarchStats) -> Self {
+        MarchReport {
+            rays: stats.rays,
+            average_iterations: stats.average_iterations(),
+            average_plain_iterations: stats.average_plain_iterations(),
+            savings: stats.savings(),
+        }
+    }
+}
+
+/// Statistics of every timing the profiler recorded, which must have room
+/// for all the measured frames.
+pub fn timing_reports(profiler: &FrameProfiler) -> Vec<TimingReport> {
+    profiler
+        .summaries()
+        .into_iter()
+        .filter_map(|(name, summary)| {
+            let stats = profiler.get(name)?;
+            Some(TimingReport {
+                name: name.to_string(),
+                samples: stats.len(),
+                average: summary.average,
+                min: stats.percentile(0.0)?,
+                p50: stats.percentile(50.0)?,
+                p90: stats.percentile(90.0)?,
+                p95: summary.p95,
+                p99: summary.p99,
+                max: stats.percentile(100.0)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<BenchmarkOptions>, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        BenchmarkOptions::from_args(&args)
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(parse(&["scene.json"]), Ok(None));
+        assert_eq!(parse(&["--benchmark"]), Ok(Some(BenchmarkOptions::default())));
+        assert_eq!(
+            parse(&[
+                "--benchmark",
+                "--warmup=10",
+                "--frames=200",
+                "--resolution=1920x1080",
+                "--output=out.json",
+                "scene.json",
+            ]),
+            Ok(Some(BenchmarkOptions {
+                warmup_frames: 10,
+                frames: 200,
+                resolution: [1920, 1080],
+                output: PathBuf::from("out.json"),
+            })),
+        );
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        for option in OPTIONS {
+            assert!(parse(&["--benchmark", option, "2000"]).is_err(), "{option}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(parse(&["--benchmark", "--resolution=0x720"]).is_err());
+        assert!(parse(&["--benchmark", "--frames=0"]).is_err());
+        for resolution in ["1280", "1280x", "x720", "1280*720", "wide x720"] {
+            let arg = format!("--resolution={resolution}");
+            assert!(parse(&["--benchmark", &arg]).is_err(), "{arg}");
+        }
+        assert!(parse(&["--benchmark", "--frames=-1"]).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        assert_eq!(
+            parse(&["--benchmark", "--fps=60"]),
+            Err(String::from("Unknown option --fps")),
+        );
+    }
+
+    #[test]
+    fn march_stats_pass_replays_the_measured_frames() {
+        let options = BenchmarkOptions { warmup_frames: 5, frames: 25, ..Default::default() };
+        assert_eq!(options.march_stats_frames(), 3);
+        assert_eq!(options.clock_time(12), 12.0 / FRAME_RATE);
+        assert_eq!(options.clock_time(30), 5.0 / FRAME_RATE);
+        assert_eq!(options.clock_time(32), 25.0 / FRAME_RATE);
+    }
+}
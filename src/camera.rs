@@ -0,0 +1,63 @@
+use serde::Deserialize;
+
+/// First person camera, looking down +Z with +Y up when `yaw` and `pitch`
+/// are zero. Angles are in radians.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Camera {
+    #[serde(default)]
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub yaw: f32,
+    #[serde(default)]
+    pub pitch: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: [0.0; 3],
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn forward(&self) -> [f32; 3] {
+        [
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.cos() * self.pitch.cos(),
+        ]
+    }
+
+    pub fn right(&self) -> [f32; 3] {
+        [self.yaw.cos(), 0.0, -self.yaw.sin()]
+    }
+
+    pub fn up(&self) -> [f32; 3] {
+        let [fx, fy, fz] = self.forward();
+        let [rx, ry, rz] = self.right();
+        [
+            fy * rz - fz * ry,
+            fz * rx - fx * rz,
+            fx * ry - fy * rx,
+        ]
+    }
+
+    /// Moves the camera along its own axes.
+    pub fn translate(&mut self, right: f32, up: f32, forward: f32) {
+        let r = self.right();
+        let f = self.forward();
+        for i in 0..3 {
+            self.position[i] += r[i] * right + f[i] * forward;
+        }
+        self.position[1] += up;
+    }
+
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-limit, limit);
+    }
+}
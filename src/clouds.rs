@@ -0,0 +1,214 @@
+use serde::Deserialize;
+
+/// Resolution of the tileable cloud noise texture along each axis.
+pub const CLOUD_NOISE_SIZE: u32 = 64;
+
+/// Horizontal layer of volumetric clouds between two altitudes.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Clouds {
+    /// Altitude of the bottom of the layer.
+    #[serde(default = "default_cloud_bottom")]
+    pub bottom: f32,
+    /// Altitude of the top of the layer.
+    #[serde(default = "default_cloud_top")]
+    pub top: f32,
+    /// Fraction of the sky covered, from 0 to 1.
+    #[serde(default = "default_cloud_coverage")]
+    pub coverage: f32,
+    /// Extinction coefficient inside the clouds.
+    #[serde(default = "default_cloud_density")]
+    pub density: f32,
+    /// Frequency of the base shape noise, per unit.
+    #[serde(default = "default_cloud_scale")]
+    pub scale: f32,
+    /// Frequency of the erosion noise relative to the base shape.
+    #[serde(default = "default_cloud_detail_scale")]
+    pub detail_scale: f32,
+    /// Velocity the clouds drift at, in units per second.
+    #[serde(default)]
+    pub wind: [f32; 3],
+    /// Henyey-Greenstein asymmetry of the forward lobe, giving the silver
+    /// lining around clouds in front of the sun.
+    #[serde(default = "default_cloud_forward_scattering")]
+    pub forward_scattering: f32,
+    /// Asymmetry of the backward lobe.
+    #[serde(default = "default_cloud_back_scattering")]
+    pub back_scattering: f32,
+}
+
+fn hash(x: u32, y: u32, z: u32, seed: u32) -> u32 {
+    // PCG hash, like the fragment shader's.
+    let pcg = |v: u32| {
+        let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+        let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+        (word >> 22) ^ word
+    };
+    pcg(x ^ pcg(y ^ pcg(z ^ pcg(seed))))
+}
+
+fn unit(v: u32) -> f32 {
+    v as f32 / u32::MAX as f32
+}
+
+fn smooth(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Gradient noise tiling every `period` units, in [0, 1].
+fn perlin(p: [f32; 3], period: u32) -> f32 {
+    const GRADIENTS: [[f32; 3]; 12] = [
+        [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+        [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+        [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+    ];
+
+    let cell = p.map(|c| c.floor());
+    let f = [p[0] - cell[0], p[1] - cell[1], p[2] - cell[2]];
+    let cell = cell.map(|c| c as i32);
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        let wrap = |c: i32| c.rem_euclid(period as i32) as u32;
+        let g = GRADIENTS[hash(wrap(cell[0] + dx), wrap(cell[1] + dy), wrap(cell[2] + dz), 0) as usize % 12];
+        g[0] * (f[0] - dx as f32) + g[1] * (f[1] - dy as f32) + g[2] * (f[2] - dz as f32)
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let [u, v, w] = f.map(smooth);
+
+    let value = lerp(
+        lerp(lerp(corner(0, 0, 0), corner(1, 0, 0), u), lerp(corner(0, 1, 0), corner(1, 1, 0), u), v),
+        lerp(lerp(corner(0, 0, 1), corner(1, 0, 1), u), lerp(corner(0, 1, 1), corner(1, 1, 1), u), v),
+        w,
+    );
+    (value * 0.5 + 0.5).clamp(0.0, 1.0)
+}
+
+/// Feature points of tileable cellular noise, one per cell of a grid
+/// covering the unit cube.
+struct WorleyGrid {
+    cells: u32,
+    points: Vec<[f32; 3]>,
+}
+
+impl WorleyGrid {
+    fn new(cells: u32) -> WorleyGrid {
+        let points = (0..cells * cells * cells)
+            .map(|i| {
+                let h = hash(i % cells, i / cells % cells, i / (cells * cells), cells);
+                [unit(h), unit(hash(h, 1, 0, 0)), unit(hash(h, 2, 0, 0))]
+            })
+            .collect();
+
+        WorleyGrid { cells, points }
+    }
+
+    /// Inverted distance to the nearest feature point, tiling every unit.
+    fn sample(&self, p: [f32; 3]) -> f32 {
+        let cells = self.cells as i32;
+        let scaled = p.map(|c| c * self.cells as f32);
+        let cell = scaled.map(|c| c.floor() as i32);
+
+        let mut min_dist = f32::MAX;
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let neighbor = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    let [x, y, z] = neighbor.map(|c| c.rem_euclid(cells));
+                    let point = self.points[(x + y * cells + z * cells * cells) as usize];
+                    let offset = [0, 1, 2].map(|i| neighbor[i] as f32 + point[i] - scaled[i]);
+                    let d = offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2];
+                    min_dist = min_dist.min(d);
+                }
+            }
+        }
+
+        1.0 - min_dist.sqrt().min(1.0)
+    }
+}
+
+/// Generates the tileable cloud noise texture: Perlin-Worley noise for the
+/// base shape in red, and Worley noise of increasing frequency in green, blue
+/// and alpha to erode it. Slices are generated in parallel.
+pub fn cloud_noise() -> Vec<[u8; 4]> {
+    let size = CLOUD_NOISE_SIZE as usize;
+    let grids: [WorleyGrid; 5] = std::array::from_fn(|octave| WorleyGrid::new(4 << octave));
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut texels = vec![[0; 4]; size * size * size];
+    let slices_per_thread = size.div_ceil(threads);
+    std::thread::scope(|scope| {
+        for (chunk_i, chunk) in texels.chunks_mut(slices_per_thread * size * size).enumerate() {
+            let grids = &grids;
+            scope.spawn(move || {
+                let first = chunk_i * slices_per_thread * size * size;
+                for (i, texel) in chunk.iter_mut().enumerate() {
+                    let i = first + i;
+                    let p = [i % size, i / size % size, i / (size * size)]
+                        .map(|c| (c as f32 + 0.5) / size as f32);
+                    *texel = cloud_noise_texel(grids, p);
+                }
+            });
+        }
+    });
+
+    texels
+}
+
+fn cloud_noise_texel(grids: &[WorleyGrid; 5], p: [f32; 3]) -> [u8; 4] {
+    let to_byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    let perlin_fbm = (0..3)
+        .map(|octave| {
+            let frequency = 4u32 << octave;
+            perlin(p.map(|c| c * frequency as f32), frequency) / (1 << octave) as f32
+        })
+        .sum::<f32>() / 1.75;
+
+    // Three octaves of Worley noise starting at each frequency.
+    let worley = grids.each_ref().map(|grid| grid.sample(p));
+    let worley_fbm = |octave: usize| {
+        worley[octave] * 0.625 + worley[octave + 1] * 0.25 + worley[octave + 2] * 0.125
+    };
+
+    // Perlin noise remapped to start at the Worley noise, keeping its billowy
+    // cells.
+    let perlin_worley = worley_fbm(0) + perlin_fbm * (1.0 - worley_fbm(0));
+
+    [
+        to_byte(perlin_worley),
+        to_byte(worley_fbm(0)),
+        to_byte(worley_fbm(1)),
+        to_byte(worley_fbm(2)),
+    ]
+}
+
+fn default_cloud_bottom() -> f32 {
+    40.0
+}
+
+fn default_cloud_top() -> f32 {
+    60.0
+}
+
+fn default_cloud_coverage() -> f32 {
+    0.5
+}
+
+fn default_cloud_density() -> f32 {
+    0.3
+}
+
+fn default_cloud_scale() -> f32 {
+    0.01
+}
+
+fn default_cloud_detail_scale() -> f32 {
+    4.0
+}
+
+fn default_cloud_forward_scattering() -> f32 {
+    0.8
+}
+
+fn default_cloud_back_scattering() -> f32 {
+    0.3
+}
@@ -0,0 +1,668 @@
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+use half::f16;
+
+/// Equirectangular environment map in linear RGB, the top row looking
+/// straight up.
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 3]>,
+}
+
+#[derive(Debug)]
+pub enum EnvironmentError {
+    Io(std::io::Error),
+    UnknownExtension(String),
+    Decode(String),
+}
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentError::Io(e) => write!(f, "could not read environment map: {e}"),
+            EnvironmentError::UnknownExtension(extension) => write!(
+                f,
+                "unsupported environment map extension \"{extension}\", expected .hdr or .exr",
+            ),
+            EnvironmentError::Decode(message) => {
+                write!(f, "could not decode environment map: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EnvironmentError {}
+
+fn decode_error<T>(message: impl Into<String>) -> Result<T, EnvironmentError> {
+    Err(EnvironmentError::Decode(message.into()))
+}
+
+/// Largest number of pixels of an environment map, which bounds what a
+/// corrupt header can make the decoders allocate.
+const MAX_PIXELS: usize = 1 << 27;
+
+/// Number of pixels of an image, checked against `MAX_PIXELS`.
+fn pixel_count(width: usize, height: usize) -> Result<usize, EnvironmentError> {
+    match width.checked_mul(height) {
+        Some(count) if count <= MAX_PIXELS => Ok(count),
+        _ => decode_error(format!("image of {width}x{height} pixels is too large")),
+    }
+}
+
+impl EnvironmentMap {
+    /// Loads a Radiance `.hdr` or OpenEXR `.exr` file, picked by extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EnvironmentMap, EnvironmentError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let data = fs::read(path).map_err(EnvironmentError::Io)?;
+
+        match extension.as_str() {
+            "hdr" => load_hdr(&data),
+            "exr" => load_exr(&data),
+            _ => Err(EnvironmentError::UnknownExtension(extension)),
+        }
+    }
+
+    /// Next level of the mip chain, averaging 2x2 blocks of pixels.
+    pub fn downsample(&self) -> EnvironmentMap {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let pixel = |x: u32, y: u32| {
+            self.pixels[(y.min(self.height - 1) * self.width + x.min(self.width - 1)) as usize]
+        };
+
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let samples = [
+                    pixel(2 * x, 2 * y),
+                    pixel(2 * x + 1, 2 * y),
+                    pixel(2 * x, 2 * y + 1),
+                    pixel(2 * x + 1, 2 * y + 1),
+                ];
+                let mut average = [0.0; 3];
+                for sample in samples {
+                    for c in 0..3 {
+                        average[c] += sample[c] * 0.25;
+                    }
+                }
+                average
+            })
+            .collect();
+
+        EnvironmentMap { width, height, pixels }
+    }
+}
+
+fn take<'a>(reader: &mut &'a [u8], count: usize) -> Result<&'a [u8], EnvironmentError> {
+    if reader.len() < count {
+        return decode_error("unexpected end of file");
+    }
+    let (bytes, rest) = reader.split_at(count);
+    *reader = rest;
+    Ok(bytes)
+}
+
+fn read_line(reader: &mut &[u8]) -> Result<String, EnvironmentError> {
+    let Some(end) = reader.iter().position(|&b| b == b'\n') else {
+        return decode_error("unexpected end of header");
+    };
+    let line = String::from_utf8_lossy(&reader[..end]).trim_end().to_string();
+    *reader = &reader[end + 1..];
+    Ok(line)
+}
+
+/// Decodes a Radiance RGBE image, see Greg Ward's "Real Pixels" in Graphics
+/// Gems II for the format.
+fn load_hdr(data: &[u8]) -> Result<EnvironmentMap, EnvironmentError> {
+    let mut reader = data;
+
+    if !read_line(&mut reader)?.starts_with("#?") {
+        return decode_error("not a Radiance HDR file");
+    }
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return decode_error(format!("unsupported pixel format {format}"));
+            }
+        }
+    }
+
+    let resolution = read_line(&mut reader)?;
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => match (width.parse::<u32>(), height.parse::<u32>()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+            _ => return decode_error(format!("invalid resolution \"{resolution}\"")),
+        },
+        _ => return decode_error(format!("unsupported orientation \"{resolution}\"")),
+    };
+
+    let mut pixels = Vec::with_capacity(pixel_count(width as usize, height as usize)?);
+    let mut scanline = vec![[0; 4]; width as usize];
+    for _ in 0..height {
+        read_hdr_scanline(&mut reader, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&[r, g, b, e]| {
+            if e == 0 {
+                [0.0; 3]
+            } else {
+                let scale = 2.0f32.powi(e as i32 - 136);
+                [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+            }
+        }));
+    }
+
+    Ok(EnvironmentMap { width, height, pixels })
+}
+
+fn read_hdr_scanline(reader: &mut &[u8], scanline: &mut [[u8; 4]]) -> Result<(), EnvironmentError> {
+    let width = scanline.len();
+    let header: [u8; 4] = take(reader, 4)?.try_into().unwrap();
+
+    if (8..0x8000).contains(&width) && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0 {
+        // Adaptive run-length encoding, each component stored separately.
+        if ((header[2] as usize) << 8 | header[3] as usize) != width {
+            return decode_error("scanline width mismatch");
+        }
+        for component in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = take(reader, 1)?[0] as usize;
+                if count > 128 {
+                    let run = count - 128;
+                    if x + run > width {
+                        return decode_error("run overflows the scanline");
+                    }
+                    let value = take(reader, 1)?[0];
+                    for pixel in &mut scanline[x..x + run] {
+                        pixel[component] = value;
+                    }
+                    x += run;
+                } else {
+                    if count == 0 || x + count > width {
+                        return decode_error("invalid scanline run");
+                    }
+                    let values = take(reader, count)?;
+                    for (pixel, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                        pixel[component] = value;
+                    }
+                    x += count;
+                }
+            }
+        }
+    } else {
+        // Flat pixels, possibly with the original (1, 1, 1, count) runs.
+        let mut pixel = header;
+        let mut shift = 0;
+        let mut x = 0;
+        loop {
+            if pixel[..3] == [1, 1, 1] {
+                let run = (pixel[3] as usize) << shift;
+                if x == 0 || x + run > width {
+                    return decode_error("invalid scanline run");
+                }
+                let previous = scanline[x - 1];
+                scanline[x..x + run].fill(previous);
+                x += run;
+                shift += 8;
+            } else {
+                scanline[x] = pixel;
+                x += 1;
+                shift = 0;
+            }
+            if x == width {
+                break;
+            }
+            pixel = take(reader, 4)?.try_into().unwrap();
+        }
+    }
+
+    Ok(())
+}
+
+const EXR_MAGIC: u32 = 20000630;
+const EXR_TILED: u32 = 0x200;
+const EXR_NON_IMAGE: u32 = 0x800;
+const EXR_MULTIPART: u32 = 0x1000;
+
+const EXR_UINT: u32 = 0;
+const EXR_HALF: u32 = 1;
+const EXR_FLOAT: u32 = 2;
+
+const EXR_NO_COMPRESSION: u8 = 0;
+const EXR_RLE_COMPRESSION: u8 = 1;
+const EXR_ZIPS_COMPRESSION: u8 = 2;
+const EXR_ZIP_COMPRESSION: u8 = 3;
+
+struct ExrChannel {
+    name: String,
+    pixel_type: u32,
+}
+
+impl ExrChannel {
+    fn size(&self) -> usize {
+        if self.pixel_type == EXR_HALF { 2 } else { 4 }
+    }
+}
+
+fn read_u32(reader: &mut &[u8]) -> Result<u32, EnvironmentError> {
+    Ok(u32::from_le_bytes(take(reader, 4)?.try_into().unwrap()))
+}
+
+fn read_i32(reader: &mut &[u8]) -> Result<i32, EnvironmentError> {
+    Ok(i32::from_le_bytes(take(reader, 4)?.try_into().unwrap()))
+}
+
+fn read_string(reader: &mut &[u8]) -> Result<String, EnvironmentError> {
+    let Some(end) = reader.iter().position(|&b| b == 0) else {
+        return decode_error("unterminated string");
+    };
+    let string = String::from_utf8_lossy(&reader[..end]).to_string();
+    *reader = &reader[end + 1..];
+    Ok(string)
+}
+
+fn parse_exr_channels(mut reader: &[u8]) -> Result<Vec<ExrChannel>, EnvironmentError> {
+    let mut channels = Vec::new();
+    loop {
+        let name = read_string(&mut reader)?;
+        if name.is_empty() {
+            return Ok(channels);
+        }
+        let pixel_type = read_u32(&mut reader)?;
+        // pLinear and reserved bytes.
+        take(&mut reader, 4)?;
+        let x_sampling = read_i32(&mut reader)?;
+        let y_sampling = read_i32(&mut reader)?;
+
+        if pixel_type > EXR_FLOAT {
+            return decode_error(format!("channel {name} has an unknown pixel type"));
+        }
+        if x_sampling != 1 || y_sampling != 1 {
+            return decode_error(format!("channel {name} is subsampled"));
+        }
+        channels.push(ExrChannel { name, pixel_type });
+    }
+}
+
+/// Decodes a single-part scanline OpenEXR image with no, RLE or ZIP
+/// compression. Only the R, G and B channels are read.
+fn load_exr(data: &[u8]) -> Result<EnvironmentMap, EnvironmentError> {
+    let mut reader = data;
+
+    if read_u32(&mut reader)? != EXR_MAGIC {
+        return decode_error("not an OpenEXR file");
+    }
+    let version = read_u32(&mut reader)?;
+    if version & EXR_TILED != 0 {
+        return decode_error("tiled OpenEXR files are not supported");
+    }
+    if version & (EXR_NON_IMAGE | EXR_MULTIPART) != 0 {
+        return decode_error("deep and multi-part OpenEXR files are not supported");
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = read_string(&mut reader)?;
+        if name.is_empty() {
+            break;
+        }
+        let _attribute_type = read_string(&mut reader)?;
+        let size = read_i32(&mut reader)?.max(0) as usize;
+        let mut value = take(&mut reader, size)?;
+
+        match name.as_str() {
+            "channels" => channels = parse_exr_channels(value)?,
+            "compression" => compression = value.first().copied(),
+            "dataWindow" => {
+                let x_min = read_i32(&mut value)?;
+                let y_min = read_i32(&mut value)?;
+                let x_max = read_i32(&mut value)?;
+                let y_max = read_i32(&mut value)?;
+                data_window = Some((x_min, y_min, x_max, y_max));
+            }
+            _ => {}
+        }
+    }
+
+    let Some((x_min, y_min, x_max, y_max)) = data_window else {
+        return decode_error("missing dataWindow attribute");
+    };
+    if x_max < x_min || y_max < y_min {
+        return decode_error("empty data window");
+    }
+    let extent = |min: i32, max: i32| max.checked_sub(min)?.checked_add(1);
+    let (Some(width), Some(height)) = (extent(x_min, x_max), extent(y_min, y_max)) else {
+        return decode_error("data window is too large");
+    };
+    let (width, height) = (width as usize, height as usize);
+    let pixel_count = pixel_count(width, height)?;
+
+    let compression = compression.unwrap_or(EXR_NO_COMPRESSION);
+    let lines_per_chunk = match compression {
+        EXR_NO_COMPRESSION | EXR_RLE_COMPRESSION | EXR_ZIPS_COMPRESSION => 1,
+        EXR_ZIP_COMPRESSION => 16,
+        _ => return decode_error(format!("unsupported compression method {compression}")),
+    };
+
+    let components: Vec<Option<usize>> = channels
+        .iter()
+        .map(|channel| match channel.name.as_str() {
+            "R" => Some(0),
+            "G" => Some(1),
+            "B" => Some(2),
+            _ => None,
+        })
+        .collect();
+    for component in 0..3 {
+        if !components.contains(&Some(component)) {
+            return decode_error("image must have R, G and B channels");
+        }
+    }
+    let line_size: usize = channels.iter().map(|c| c.size() * width).sum();
+
+    let chunk_count = height.div_ceil(lines_per_chunk);
+    // The offset table must be there before allocating the whole image.
+    if reader.len() < chunk_count * 8 {
+        return decode_error("unexpected end of file");
+    }
+    let mut pixels = vec![[0.0; 3]; pixel_count];
+    for _ in 0..chunk_count {
+        let offset = u64::from_le_bytes(take(&mut reader, 8)?.try_into().unwrap()) as usize;
+        let mut chunk = data.get(offset..).unwrap_or_default();
+
+        let first_line = read_i32(&mut chunk)?.checked_sub(y_min);
+        let size = read_i32(&mut chunk)?.max(0) as usize;
+        let compressed = take(&mut chunk, size)?;
+
+        let Some(first_line) = first_line
+            .and_then(|line| usize::try_from(line).ok())
+            .filter(|&line| line < height)
+        else {
+            return decode_error("chunk outside of the data window");
+        };
+        let line_count = lines_per_chunk.min(height - first_line);
+        let expected = line_count * line_size;
+
+        let lines = if size == expected {
+            // Chunks that do not shrink are stored uncompressed.
+            compressed.to_vec()
+        } else {
+            match compression {
+                EXR_RLE_COMPRESSION => reconstruct_exr_bytes(decompress_exr_rle(compressed)?),
+                EXR_ZIPS_COMPRESSION | EXR_ZIP_COMPRESSION => {
+                    let mut bytes = Vec::with_capacity(expected);
+                    ZlibDecoder::new(compressed)
+                        .read_to_end(&mut bytes)
+                        .or_else(|e| decode_error(format!("invalid zip data: {e}")))?;
+                    reconstruct_exr_bytes(bytes)
+                }
+                _ => return decode_error("chunk size mismatch"),
+            }
+        };
+        if lines.len() != expected {
+            return decode_error("chunk size mismatch");
+        }
+
+        let mut lines = lines.as_slice();
+        for y in first_line..first_line + line_count {
+            for (channel, component) in channels.iter().zip(&components) {
+                let values = take(&mut lines, channel.size() * width)?;
+                let Some(component) = *component else {
+                    continue;
+                };
+                for (x, value) in values.chunks_exact(channel.size()).enumerate() {
+                    pixels[y * width + x][component] = match channel.pixel_type {
+                        EXR_HALF => f16::from_le_bytes(value.try_into().unwrap()).to_f32(),
+                        EXR_UINT => u32::from_le_bytes(value.try_into().unwrap()) as f32,
+                        _ => f32::from_le_bytes(value.try_into().unwrap()),
+                    };
+                }
+            }
+        }
+    }
+
+    Ok(EnvironmentMap {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+    })
+}
+
+fn decompress_exr_rle(mut reader: &[u8]) -> Result<Vec<u8>, EnvironmentError> {
+    let mut bytes = Vec::new();
+    while !reader.is_empty() {
+        let count = take(&mut reader, 1)?[0] as i8;
+        if count < 0 {
+            bytes.extend_from_slice(take(&mut reader, count.unsigned_abs() as usize)?);
+        } else {
+            let value = take(&mut reader, 1)?[0];
+            bytes.extend(std::iter::repeat_n(value, count as usize + 1));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Undoes the delta predictor and byte split applied by the RLE and ZIP
+/// compressors before compressing.
+fn reconstruct_exr_bytes(mut bytes: Vec<u8>) -> Vec<u8> {
+    for i in 1..bytes.len() {
+        bytes[i] = bytes[i - 1].wrapping_add(bytes[i]).wrapping_sub(128);
+    }
+
+    let half = bytes.len().div_ceil(2);
+    (0..bytes.len())
+        .map(|i| if i % 2 == 0 { bytes[i / 2] } else { bytes[half + i / 2] })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn hdr_file(width: u32, height: u32, scanlines: &[u8]) -> Vec<u8> {
+        let mut data = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n")
+            .into_bytes();
+        data.extend_from_slice(scanlines);
+        data
+    }
+
+    fn is_decode_error(result: Result<EnvironmentMap, EnvironmentError>) -> bool {
+        matches!(result, Err(EnvironmentError::Decode(_)))
+    }
+
+    #[test]
+    fn hdr_flat_scanlines() {
+        // 128 * 2^(129 - 136) = 1, then a (1, 1, 1, 2) run repeating it.
+        let map = load_hdr(&hdr_file(4, 1, &[128, 64, 0, 129, 0, 0, 0, 0, 1, 1, 1, 2])).unwrap();
+        assert_eq!((map.width, map.height), (4, 1));
+        assert_eq!(map.pixels, [[1.0, 0.5, 0.0], [0.0; 3], [0.0; 3], [0.0; 3]]);
+    }
+
+    #[test]
+    fn hdr_rle_scanlines() {
+        let mut scanlines = Vec::new();
+        for _ in 0..2 {
+            scanlines.extend_from_slice(&[2, 2, 0, 8]);
+            // R and G as runs, B as literals, E as a run.
+            scanlines.extend_from_slice(&[128 + 8, 128, 128 + 8, 64]);
+            scanlines.extend_from_slice(&[8, 0, 0, 0, 0, 0, 0, 0, 32]);
+            scanlines.extend_from_slice(&[128 + 8, 129]);
+        }
+        let map = load_hdr(&hdr_file(8, 2, &scanlines)).unwrap();
+        assert_eq!((map.width, map.height), (8, 2));
+        assert_eq!(map.pixels.len(), 16);
+        assert_eq!(map.pixels[0], [1.0, 0.5, 0.0]);
+        assert_eq!(map.pixels[7], [1.0, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn hdr_truncated() {
+        let scanlines = [2, 2, 0, 8, 128 + 8, 128, 128 + 8];
+        assert!(is_decode_error(load_hdr(&hdr_file(8, 1, &scanlines))));
+        assert!(is_decode_error(load_hdr(&hdr_file(4, 1, &[128, 64, 0, 129]))));
+        assert!(is_decode_error(load_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n")));
+    }
+
+    #[test]
+    fn hdr_oversized() {
+        // The product overflows a u32.
+        assert!(is_decode_error(load_hdr(&hdr_file(65536, 65536, &[]))));
+        assert!(is_decode_error(load_hdr(&hdr_file(100000, 10000, &[]))));
+    }
+
+    /// Inverse of `reconstruct_exr_bytes`.
+    fn predict_exr_bytes(bytes: &[u8]) -> Vec<u8> {
+        let mut split: Vec<u8> = bytes.iter().step_by(2).copied().collect();
+        split.extend(bytes.iter().skip(1).step_by(2));
+        let mut predicted = split.clone();
+        for i in 1..split.len() {
+            predicted[i] = split[i].wrapping_sub(split[i - 1]).wrapping_add(128);
+        }
+        predicted
+    }
+
+    /// RLE made of literal runs only.
+    fn compress_exr_rle(bytes: &[u8]) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        for run in bytes.chunks(127) {
+            compressed.push(-(run.len() as i8) as u8);
+            compressed.extend_from_slice(run);
+        }
+        compressed
+    }
+
+    fn compress_exr_zip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn exr_attribute(data: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+        for string in [name, attribute_type] {
+            data.extend_from_slice(string.as_bytes());
+            data.push(0);
+        }
+        data.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        data.extend_from_slice(value);
+    }
+
+    /// OpenEXR file of half float B, G and R channels, with the data window
+    /// `[x_min, x_max] x [0, pixels.len() - 1]`.
+    fn exr_file(compression: u8, x_min: i32, x_max: i32, pixels: &[Vec<[f32; 3]>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&EXR_MAGIC.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+
+        let mut channels = Vec::new();
+        for name in ["B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&EXR_HALF.to_le_bytes());
+            channels.extend_from_slice(&[0; 4]);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+        exr_attribute(&mut data, "channels", "chlist", &channels);
+        exr_attribute(&mut data, "compression", "compression", &[compression]);
+        let window = [x_min, 0, x_max, pixels.len() as i32 - 1];
+        let window: Vec<u8> = window.iter().flat_map(|value| value.to_le_bytes()).collect();
+        exr_attribute(&mut data, "dataWindow", "box2i", &window);
+        data.push(0);
+
+        let lines_per_chunk = if compression == EXR_ZIP_COMPRESSION { 16 } else { 1 };
+        let chunks: Vec<Vec<u8>> = pixels
+            .chunks(lines_per_chunk)
+            .map(|lines| {
+                let mut bytes = Vec::new();
+                for line in lines {
+                    for component in [2, 1, 0] {
+                        for pixel in line {
+                            bytes.extend_from_slice(&f16::from_f32(pixel[component]).to_le_bytes());
+                        }
+                    }
+                }
+                match compression {
+                    EXR_RLE_COMPRESSION => compress_exr_rle(&predict_exr_bytes(&bytes)),
+                    EXR_ZIPS_COMPRESSION | EXR_ZIP_COMPRESSION => {
+                        compress_exr_zip(&predict_exr_bytes(&bytes))
+                    }
+                    _ => bytes,
+                }
+            })
+            .collect();
+
+        let mut offset = data.len() + 8 * chunks.len();
+        for chunk in &chunks {
+            data.extend_from_slice(&(offset as u64).to_le_bytes());
+            offset += 8 + chunk.len();
+        }
+        for (i, chunk) in chunks.iter().enumerate() {
+            data.extend_from_slice(&((i * lines_per_chunk) as i32).to_le_bytes());
+            data.extend_from_slice(&(chunk.len() as i32).to_le_bytes());
+            data.extend_from_slice(chunk);
+        }
+        data
+    }
+
+    fn exr_pixels() -> Vec<Vec<[f32; 3]>> {
+        (0..3)
+            .map(|y| (0..4).map(|x| [x as f32, y as f32, 0.5]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn exr_compressions() {
+        let pixels = exr_pixels();
+        for compression in [
+            EXR_NO_COMPRESSION,
+            EXR_RLE_COMPRESSION,
+            EXR_ZIPS_COMPRESSION,
+            EXR_ZIP_COMPRESSION,
+        ] {
+            let map = load_exr(&exr_file(compression, 0, 3, &pixels)).unwrap();
+            assert_eq!((map.width, map.height), (4, 3), "compression {compression}");
+            assert_eq!(map.pixels, pixels.concat(), "compression {compression}");
+        }
+    }
+
+    #[test]
+    fn exr_truncated() {
+        for compression in [EXR_NO_COMPRESSION, EXR_RLE_COMPRESSION, EXR_ZIP_COMPRESSION] {
+            let data = exr_file(compression, 0, 3, &exr_pixels());
+            for length in [4, data.len() / 2, data.len() - 1] {
+                assert!(is_decode_error(load_exr(&data[..length])), "compression {compression}");
+            }
+        }
+    }
+
+    #[test]
+    fn exr_oversized() {
+        let lines = [vec![[0.0; 3]; 1]];
+        // The width overflows an i32.
+        let data = exr_file(EXR_NO_COMPRESSION, i32::MIN, i32::MAX, &lines);
+        assert!(is_decode_error(load_exr(&data)));
+        let data = exr_file(EXR_NO_COMPRESSION, 0, 1 << 30, &lines);
+        assert!(is_decode_error(load_exr(&data)));
+    }
+}
@@ -0,0 +1,17 @@
+//! Vulkan ray marcher of signed distance field scenes. The binary drives a
+//! `vulkan::VulkanContext` from a window; `profiling` holds the frame timing
+//! statistics it gathers and `benchmark` the report of the `--benchmark` mode.
+
+pub mod atmosphere;
+pub mod benchmark;
+pub mod camera;
+pub mod clouds;
+pub mod environment;
+pub mod lut;
+pub mod post;
+pub mod profiling;
+pub mod resolution;
+pub mod scene;
+pub mod settings;
+pub mod volume;
+pub mod vulkan;
@@ -0,0 +1,248 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::SplitWhitespace;
+
+/// Largest size of the 3D table a 1D LUT is expanded into.
+const MAX_EXPANDED_1D_SIZE: usize = 65;
+
+/// Color grading lookup table read from a `.cube` file, as a 3D table. 1D
+/// LUTs are expanded into one, their channels being independent.
+#[derive(Debug, Clone)]
+pub struct Lut {
+    /// Number of entries along each axis of the table.
+    pub size: u32,
+    /// Input colors mapped to the first and last entries.
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    /// Output colors, red varying fastest then green.
+    pub table: Vec<[f32; 3]>,
+}
+
+#[derive(Debug)]
+pub enum LutError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+    /// Valid `.cube` feature this loader doesn't handle.
+    Unsupported { line: usize, feature: String },
+}
+
+impl fmt::Display for LutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LutError::Io(e) => write!(f, "could not read LUT: {e}"),
+            LutError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            LutError::Unsupported { line, feature } => {
+                write!(f, "line {line}: {feature} are not supported")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LutError {}
+
+fn parse_error<T>(line: usize, message: impl Into<String>) -> Result<T, LutError> {
+    Err(LutError::Parse { line, message: message.into() })
+}
+
+impl Lut {
+    /// Loads a `.cube` file in the Adobe or Resolve flavor.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Lut, LutError> {
+        let source = fs::read_to_string(path).map_err(LutError::Io)?;
+        Lut::parse(&source)
+    }
+
+    pub fn parse(source: &str) -> Result<Lut, LutError> {
+        let mut size_1d = None;
+        let mut size_3d = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut domain_line = 0;
+        let mut entries = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+            if !keyword.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                entries.push(parse_numbers::<3>(line.split_whitespace(), number)?);
+                continue;
+            }
+            if !entries.is_empty() {
+                return parse_error(number, format!("{keyword} after the table entries"));
+            }
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let declared = match keyword {
+                        "LUT_1D_SIZE" => size_1d,
+                        _ => size_3d,
+                    };
+                    if declared.is_some() {
+                        return parse_error(number, format!("{keyword} declared twice"));
+                    }
+                    // Resolve's combined form, a 1D shaper before the 3D table.
+                    if size_1d.is_some() || size_3d.is_some() {
+                        return Err(LutError::Unsupported {
+                            line: number,
+                            feature: String::from("LUTs combining a 1D shaper and a 3D table"),
+                        });
+                    }
+                    let [size] = parse_numbers::<1>(words, number)?;
+                    let max_size = if keyword == "LUT_1D_SIZE" { 65536.0 } else { 256.0 };
+                    if size.fract() != 0.0 || !(2.0..=max_size).contains(&size) {
+                        return parse_error(
+                            number,
+                            format!("{keyword} must be an integer from 2 to {max_size}"),
+                        );
+                    }
+                    match keyword {
+                        "LUT_1D_SIZE" => size_1d = Some(size as usize),
+                        _ => size_3d = Some(size as usize),
+                    }
+                }
+                "DOMAIN_MIN" => {
+                    domain_min = parse_numbers::<3>(words, number)?;
+                    domain_line = number;
+                }
+                "DOMAIN_MAX" => {
+                    domain_max = parse_numbers::<3>(words, number)?;
+                    domain_line = number;
+                }
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {
+                    let [min, max] = parse_numbers::<2>(words, number)?;
+                    domain_min = [min; 3];
+                    domain_max = [max; 3];
+                    domain_line = number;
+                }
+                _ => return parse_error(number, format!("unknown keyword \"{keyword}\"")),
+            }
+        }
+
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            return parse_error(domain_line, "domain maximum must be above its minimum");
+        }
+
+        let last_line = source.lines().count().max(1);
+        let (size, expected) = match (size_1d, size_3d) {
+            (Some(size), _) => (size, size),
+            (_, Some(size)) => (size, size * size * size),
+            (None, None) => return parse_error(last_line, "missing LUT_1D_SIZE or LUT_3D_SIZE"),
+        };
+        if entries.len() != expected {
+            return parse_error(
+                last_line,
+                format!("expected {expected} table entries, found {}", entries.len()),
+            );
+        }
+
+        let (size, table) = match size_1d {
+            Some(_) => expand_1d(&entries),
+            None => (size, entries),
+        };
+
+        Ok(Lut {
+            size: size as u32,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+}
+
+/// Parses the rest of a line as exactly `N` finite numbers.
+fn parse_numbers<const N: usize>(words: SplitWhitespace, line: usize) -> Result<[f32; N], LutError> {
+    let words: Vec<_> = words.collect();
+    if words.len() != N {
+        return parse_error(line, format!("expected {N} numbers, found {}", words.len()));
+    }
+
+    let mut numbers = [0.0; N];
+    for (number, word) in numbers.iter_mut().zip(words) {
+        *number = match word.parse::<f32>() {
+            Ok(value) if value.is_finite() => value,
+            _ => return parse_error(line, format!("invalid number \"{word}\"")),
+        };
+    }
+
+    Ok(numbers)
+}
+
+/// Expands the curves of a 1D LUT into a 3D table, resampling them linearly
+/// when they are larger than `MAX_EXPANDED_1D_SIZE`.
+fn expand_1d(curves: &[[f32; 3]]) -> (usize, Vec<[f32; 3]>) {
+    let size = curves.len().min(MAX_EXPANDED_1D_SIZE);
+    let curve = |channel: usize, i: usize| {
+        let position = i as f32 / (size - 1) as f32 * (curves.len() - 1) as f32;
+        let below = (position as usize).min(curves.len() - 2);
+        let t = position - below as f32;
+        curves[below][channel] * (1.0 - t) + curves[below + 1][channel] * t
+    };
+
+    let table = (0..size * size * size)
+        .map(|i| [curve(0, i % size), curve(1, i / size % size), curve(2, i / (size * size))])
+        .collect();
+
+    (size, table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error_line(source: &str) -> Option<usize> {
+        match Lut::parse(source) {
+            Err(LutError::Parse { line, .. }) => Some(line),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_3d_table() {
+        let mut source = String::from("TITLE \"identity\"\nLUT_3D_SIZE 2\n");
+        for i in 0..8 {
+            source += &format!("{} {} {}\n", i & 1, i >> 1 & 1, i >> 2);
+        }
+        let lut = Lut::parse(&source).unwrap();
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.table[5], [1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_bad_size() {
+        assert_eq!(parse_error_line("# comment\nLUT_3D_SIZE 1\n"), Some(2));
+        assert_eq!(parse_error_line("LUT_3D_SIZE 2.5\n"), Some(1));
+        assert_eq!(parse_error_line("LUT_1D_SIZE 70000\n"), Some(1));
+        assert_eq!(parse_error_line("LUT_3D_SIZE 2\nLUT_3D_SIZE 2\n"), Some(2));
+    }
+
+    #[test]
+    fn rejects_wrong_entry_count() {
+        assert_eq!(parse_error_line("LUT_1D_SIZE 3\n0 0 0\n1 1 1\n"), Some(3));
+    }
+
+    #[test]
+    fn rejects_non_numeric_entry() {
+        assert_eq!(parse_error_line("LUT_1D_SIZE 2\n0 0 0\n1 x 1\n"), Some(3));
+        assert_eq!(parse_error_line("LUT_1D_SIZE 2\n0 0 0\n1 1\n"), Some(3));
+    }
+
+    #[test]
+    fn rejects_empty_domain() {
+        let source = "LUT_1D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 0 1\n0 0 0\n1 1 1\n";
+        assert_eq!(parse_error_line(source), Some(3));
+        assert_eq!(parse_error_line("LUT_1D_SIZE 2\nLUT_1D_INPUT_RANGE 1 -1\n"), Some(2));
+    }
+
+    #[test]
+    fn reports_combined_form_as_unsupported() {
+        let source = "LUT_1D_SIZE 2\nLUT_3D_SIZE 2\n";
+        assert!(matches!(Lut::parse(source), Err(LutError::Unsupported { line: 2, .. })));
+    }
+}
@@ -1,26 +1,152 @@
 use core::panic;
-use std::{sync::Arc, usize};
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Instant, usize};
 
 use vulkano::{swapchain::{self, SwapchainPresentInfo}, sync::{self, future::FenceSignalFuture, GpuFuture}, Validated, VulkanError};
-use winit::{dpi::LogicalSize, event::{Event, WindowEvent}, event_loop::{ControlFlow, EventLoop}, window::WindowBuilder};
+use winit::{dpi::{LogicalSize, PhysicalSize}, event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent}, event_loop::{ControlFlow, EventLoop}, window::WindowBuilder};
 
-mod vulkan;
+use vk_ray_marcher::{atmosphere, benchmark, camera, environment, lut, post, profiling, resolution, scene, settings, volume, vulkan};
+
+const WINDOW_TITLE: &str = "vk-ray-marcher";
+
+/// Camera movement speed, in units per second.
+const CAMERA_SPEED: f32 = 2.0;
+/// Camera turn speed, in radians per second.
+const CAMERA_TURN_SPEED: f32 = 1.5;
+/// Environment rotation step of the bracket keys, in radians.
+const ENVIRONMENT_ROTATION_STEP: f32 = std::f32::consts::PI / 12.0;
+/// Environment intensity factor of the minus and equals keys.
+const ENVIRONMENT_INTENSITY_STEP: f32 = 1.25;
+/// Time of day step of the comma and period keys, in hours.
+const TIME_OF_DAY_STEP: f32 = 0.5;
+/// Exposure step of the 9 and 0 keys, in stops.
+const EXPOSURE_STEP: f32 = 0.5;
+/// Color grading strength step of the semicolon and apostrophe keys.
+const COLOR_GRADING_STEP: f32 = 0.1;
+/// Relaxation factor step of the Home and End keys.
+const RELAXATION_STEP: f32 = 0.1;
+/// Render scale step of the Insert and Delete keys.
+const RENDER_SCALE_STEP: f32 = 0.1;
+/// Largest supersampling grid the G key cycles through, per axis.
+const MAX_SUPERSAMPLING_GRID: u32 = 4;
+/// Interval between two reports of the march statistics, in seconds.
+const MARCH_STATS_INTERVAL: f32 = 1.0;
+/// Interval between two reports of the frame timings, in seconds.
+const TIMINGS_INTERVAL: f32 = 5.0;
 
 fn main() {
+    // Options start with `--`, the first other argument is the scene.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let backend = if args.iter().any(|arg| arg == "--compute") {
+        vulkan::Backend::Compute
+    } else {
+        vulkan::Backend::Graphics
+    };
+
+    let benchmark = benchmark::BenchmarkOptions::from_args(&args)
+        .unwrap_or_else(|e| panic!("Failed to parse benchmark options: {e}"));
+
+    let scene_path = args.iter().find(|arg| !arg.starts_with("--")).cloned();
+    let scene = match &scene_path {
+        Some(path) => scene::Scene::load(path)
+            .unwrap_or_else(|e| panic!("Failed to load scene {path}: {e}")),
+        None => scene::Scene::default(),
+    };
+
+    let environment_map = scene.environment.map.as_ref().and_then(|path| {
+        match environment::EnvironmentMap::load(path) {
+            Ok(map) => Some(map),
+            Err(e) => {
+                println!("Failed to load environment map {}: {e}, using the procedural sky", path.display());
+                None
+            }
+        }
+    });
+
+    let volume_texture = scene.volume_texture().map(|(path, resolution)| {
+        volume::VolumeTexture::load(path, resolution).unwrap_or_else(|e| {
+            panic!("Failed to load volume texture {}: {e}", path.display())
+        })
+    });
+
+    let lut = scene.color_grading_lut().map(|path| {
+        lut::Lut::load(path).unwrap_or_else(|e| {
+            panic!("Failed to load color grading LUT {}: {e}", path.display())
+        })
+    });
+
     let event_loop = EventLoop::new();
 
-    let window = Arc::new(
-        WindowBuilder::new()
-//            .with_resizable(false)
-            .with_inner_size(LogicalSize::<u32> {
-                width: 1280,
-                height: 720,
-            })
-            .build(&event_loop)
-            .unwrap()
-        );
+    let mut window_builder = WindowBuilder::new()
+        .with_title(WINDOW_TITLE)
+//        .with_resizable(false)
+        .with_inner_size(LogicalSize::<u32> {
+            width: 1280,
+            height: 720,
+        });
+    // The benchmark renders a fixed number of pixels.
+    if let Some(options) = &benchmark {
+        let [width, height] = options.resolution;
+        window_builder = window_builder
+            .with_inner_size(PhysicalSize::new(width, height))
+            .with_resizable(false);
+    }
+    let window = Arc::new(window_builder.build(&event_loop).unwrap());
 
-    let mut context = vulkan::VulkanContext::new(&event_loop, &window);
+    let mut settings = settings::RenderSettings {
+        environment_rotation: scene.environment.rotation,
+        environment_intensity: scene.environment.intensity,
+        time_of_day: scene.environment.atmosphere
+            .as_ref()
+            .map_or(12.0, |atmosphere| atmosphere.time_of_day),
+        sun_animation: scene.environment.atmosphere
+            .as_ref()
+            .is_some_and(|atmosphere| atmosphere.animate),
+        post: scene.post.clone(),
+        ..Default::default()
+    };
+    let mut camera = scene.camera;
+    let mut pressed_keys = HashSet::new();
+    let start_time = Instant::now();
+    let mut last_frame = start_time;
+    let mut last_stats_report = start_time;
+    let mut last_timings_report = start_time;
+    let mut print_timings = true;
+    let mut title = String::from(WINDOW_TITLE);
+
+    let mut context = vulkan::VulkanContext::new(
+        &event_loop,
+        &window,
+        &scene,
+        environment_map,
+        volume_texture,
+        lut,
+        &settings,
+        backend,
+    );
+
+    if settings.dynamic_resolution && context.timestamp_pool.is_none() {
+        println!("The GPU can't measure frame times, the dynamic resolution is disabled");
+        settings.dynamic_resolution = false;
+    }
+
+    // Frames submitted so far, counted by the benchmark.
+    let mut frame_count = 0;
+    // Timings of the measured frames, kept while the march statistics are
+    // counted.
+    let mut benchmark_timings = Vec::new();
+    if let Some(options) = &benchmark {
+        settings.dynamic_resolution = false;
+        settings.render_scale = 1.0;
+        // Only the measured frames' GPU times are kept.
+        context.profile_gpu = false;
+        println!(
+            "Benchmarking {} frames at {}x{} after {} warm-up frames",
+            options.frames,
+            options.resolution[0],
+            options.resolution[1],
+            options.warmup_frames,
+        );
+    }
 
     let mut window_resized = false;
     let mut recreate_swapchain = false;
@@ -42,13 +168,105 @@ fn main() {
         } => {
             window_resized = true;
         }
+        Event::WindowEvent {
+            event: WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+                ..
+            },
+            ..
+        } => match state {
+            // The benchmark's settings and camera are fixed.
+            _ if benchmark.is_some() => (),
+            // Key repeats are ignored, held keys are only handled once.
+            ElementState::Pressed => {
+                if !pressed_keys.insert(keycode) {
+                    return;
+                }
+                if keycode == VirtualKeyCode::Grave {
+                    print_timings = !print_timings;
+                    println!("Frame timings: {print_timings}");
+                } else if handle_hotkey(keycode, &mut settings) {
+                    context.reset_accumulation();
+                }
+            }
+            ElementState::Released => {
+                pressed_keys.remove(&keycode);
+            }
+        },
         Event::MainEventsCleared => {
+            let now = Instant::now();
+            let mut delta_time = (now - last_frame).as_secs_f32();
+            last_frame = now;
+            context.profiler.add("CPU frame", delta_time * 1000.0);
+            let mut time = (now - start_time).as_secs_f32();
+
+            if let Some(options) = &benchmark {
+                let measured_end = options.warmup_frames + options.frames;
+                if frame_count == options.warmup_frames {
+                    // Keeps every measured frame.
+                    context.profiler = profiling::FrameProfiler::new(options.frames as usize);
+                    context.profile_gpu = true;
+                }
+                if frame_count == measured_end
+                    || frame_count == measured_end + options.march_stats_frames()
+                {
+                    // Nothing else submits to the device while it drains.
+                    unsafe { context.device.wait_idle() }.expect("Could not wait for the device.");
+                    context.read_remaining_submissions();
+                }
+                if frame_count == measured_end {
+                    // Counting the iterations slows the frames down, so it
+                    // runs after the measurements.
+                    benchmark_timings = benchmark::timing_reports(&context.profiler);
+                    context.profile_gpu = false;
+                    context.take_march_stats();
+                    settings.march_stats = true;
+                } else if frame_count == measured_end + options.march_stats_frames() {
+                    let march_stats = context.take_march_stats();
+                    write_benchmark_report(
+                        options,
+                        scene_path.as_deref(),
+                        backend,
+                        &settings,
+                        &context,
+                        std::mem::take(&mut benchmark_timings),
+                        march_stats,
+                    );
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+
+                delta_time = 1.0 / benchmark::FRAME_RATE;
+                time = options.clock_time(frame_count);
+                camera = benchmark::camera_path(&scene.camera, time);
+                context.reset_accumulation();
+            } else if update_camera(&mut camera, &pressed_keys, delta_time) {
+                context.reset_accumulation();
+            }
+
+            let sun = match &scene.environment.atmosphere {
+                Some(atmosphere) => {
+                    if settings.sun_animation {
+                        settings.time_of_day = (settings.time_of_day
+                            + atmosphere.day_speed * delta_time).rem_euclid(24.0);
+                        context.reset_accumulation();
+                    }
+                    atmosphere.sun(settings.time_of_day)
+                }
+                None => atmosphere::Sun::default(),
+            };
+
             if window_resized || recreate_swapchain {
                 recreate_swapchain = false;
                 context.recreate_swapchain(&window, window_resized);
                 window_resized = false;
             }
 
+            let acquire_start = Instant::now();
             let (image_i, suboptimal, acquire_future) =
                 match swapchain::acquire_next_image(
                     context.swapchain.clone(),
@@ -61,6 +279,7 @@ fn main() {
                     }
                     Err(_) => panic!("Failed to acquire next image"),
                 };
+            context.profiler.add("Acquire", elapsed_ms(acquire_start));
 
             if suboptimal {
                 recreate_swapchain = true;
@@ -70,6 +289,59 @@ fn main() {
                 image_fence.wait(None).unwrap();
             }
 
+            // Drifting clouds change the image every frame.
+            if settings.clouds && scene.clouds.as_ref().is_some_and(|clouds| clouds.wind != [0.0; 3]) {
+                context.reset_accumulation();
+            }
+
+            context.update_frame(image_i as usize, &settings, &camera, &sun, time);
+
+            if settings.dynamic_resolution && context.render_scale != settings.render_scale {
+                println!("Render scale: {:.0}%", context.render_scale * 100.0);
+            }
+            // Manual adjustments start from the dynamic resolution's scale.
+            settings.render_scale = context.render_scale;
+
+            // The benchmark reports the march statistics itself.
+            if benchmark.is_none()
+                && (now - last_stats_report).as_secs_f32() >= MARCH_STATS_INTERVAL
+            {
+                last_stats_report = now;
+                let stats = context.take_march_stats();
+                if stats.rays > 0 {
+                    println!(
+                        "Average iterations: {:.1}, {:.1} with plain sphere tracing ({:.1}% saved)",
+                        stats.average_iterations(),
+                        stats.average_plain_iterations(),
+                        stats.savings() * 100.0,
+                    );
+                }
+            }
+
+            if print_timings && (now - last_timings_report).as_secs_f32() >= TIMINGS_INTERVAL {
+                last_timings_report = now;
+                println!("Frame timings over the last {} frames:", profiling::STATS_WINDOW);
+                for (name, summary) in context.profiler.summaries() {
+                    println!(
+                        "  {name}: {:.2} ms average, {:.2} ms p95, {:.2} ms p99",
+                        summary.average,
+                        summary.p95,
+                        summary.p99,
+                    );
+                }
+            }
+
+            let new_title = match settings.render_mode {
+                settings::RenderMode::PathTraced => {
+                    format!("{WINDOW_TITLE} - {} samples", context.sample_count)
+                }
+                settings::RenderMode::Direct => String::from(WINDOW_TITLE),
+            };
+            if new_title != title {
+                window.set_title(&new_title);
+                title = new_title;
+            }
+
             let previous_future = match fences[previous_fence_i as usize].clone() {
                 None => {
                     let mut now = sync::now(context.device.clone());
@@ -80,13 +352,23 @@ fn main() {
                 Some(fence) => fence.boxed(),
             };
 
-            let future = previous_future
+            // The command buffer is submitted before the present is queued,
+            // so that both are timed apart.
+            let submit_start = Instant::now();
+            let execute_future = previous_future
                 .join(acquire_future)
                 .then_execute(
                     context.queue.clone(),
                     context.command_buffers[image_i as usize].clone(),
                 )
-                .unwrap()
+                .unwrap();
+            if let Err(e) = execute_future.flush() {
+                println!("Failed to submit command buffer: {e}");
+            }
+            context.profiler.add("Submit", elapsed_ms(submit_start));
+
+            let present_start = Instant::now();
+            let future = execute_future
                 .then_swapchain_present(
                     context.queue.clone(),
                     SwapchainPresentInfo::swapchain_image_index(
@@ -95,6 +377,7 @@ fn main() {
                     )
                 )
                 .then_signal_fence_and_flush();
+            context.profiler.add("Present", elapsed_ms(present_start));
 
             fences[image_i as usize] = match future.map_err(Validated::unwrap) {
                 Ok(value) => Some(Arc::new(value)),
@@ -109,7 +392,311 @@ fn main() {
             };
 
             previous_fence_i = image_i;
+            frame_count += 1;
         }
         _ => (),
     });
 }
+
+/// Writes the report of a finished benchmark, with the timings of the
+/// measured frames and the march statistics counted after them.
+fn write_benchmark_report(
+    options: &benchmark::BenchmarkOptions,
+    scene_path: Option<&str>,
+    backend: vulkan::Backend,
+    settings: &settings::RenderSettings,
+    context: &vulkan::VulkanContext,
+    timings: Vec<benchmark::TimingReport>,
+    march_stats: vulkan::MarchStats,
+) {
+    let report = benchmark::BenchmarkReport {
+        scene: scene_path.map(PathBuf::from),
+        backend: format!("{backend:?}"),
+        resolution: context.window_extent,
+        warmup_frames: options.warmup_frames,
+        frames: options.frames,
+        device: benchmark::DeviceInfo::new(context.device.physical_device().properties()),
+        settings: benchmark::SettingsInfo::new(settings),
+        timings,
+        march_stats: benchmark::MarchReport::new(&march_stats),
+    };
+
+    match report.write(&options.output) {
+        Ok(()) => println!("Benchmark report written to {}", options.output.display()),
+        Err(e) => println!("Failed to write benchmark report {}: {e}", options.output.display()),
+    }
+}
+
+/// Time elapsed since `start`, in milliseconds.
+fn elapsed_ms(start: Instant) -> f32 {
+    start.elapsed().as_secs_f32() * 1000.0
+}
+
+/// Applies the render setting bound to the key, returns whether the traced
+/// radiance changed, which invalidates the path traced samples. Settings of
+/// the denoising, resolve, post-processing and display stages, and
+/// optimizations that shouldn't change what the rays hit, leave it as is.
+fn handle_hotkey(keycode: VirtualKeyCode, settings: &mut settings::RenderSettings) -> bool {
+    match keycode {
+        VirtualKeyCode::F1 => {
+            settings.shadows = !settings.shadows;
+            println!("Soft shadows: {}", settings.shadows);
+            true
+        }
+        VirtualKeyCode::F2 => {
+            settings.ambient_occlusion = !settings.ambient_occlusion;
+            println!("Ambient occlusion: {}", settings.ambient_occlusion);
+            true
+        }
+        VirtualKeyCode::F3 => {
+            settings.shading_model = match settings.shading_model {
+                settings::ShadingModel::BlinnPhong => settings::ShadingModel::Pbr,
+                settings::ShadingModel::Pbr => settings::ShadingModel::BlinnPhong,
+            };
+            println!("Shading model: {:?}", settings.shading_model);
+            true
+        }
+        VirtualKeyCode::PageUp => {
+            settings.max_bounces += 1;
+            println!("Max bounces: {}", settings.max_bounces);
+            true
+        }
+        VirtualKeyCode::PageDown => {
+            settings.max_bounces = settings.max_bounces.saturating_sub(1);
+            println!("Max bounces: {}", settings.max_bounces);
+            true
+        }
+        VirtualKeyCode::F4 => {
+            settings.render_mode = match settings.render_mode {
+                settings::RenderMode::Direct => settings::RenderMode::PathTraced,
+                settings::RenderMode::PathTraced => settings::RenderMode::Direct,
+            };
+            println!("Render mode: {:?}", settings.render_mode);
+            true
+        }
+        VirtualKeyCode::F5 => {
+            settings.denoiser.enabled = !settings.denoiser.enabled;
+            println!("Denoiser: {}", settings.denoiser.enabled);
+            false
+        }
+        VirtualKeyCode::F6 => {
+            settings.volumetrics = !settings.volumetrics;
+            println!("Volumetrics: {}", settings.volumetrics);
+            true
+        }
+        VirtualKeyCode::F7 => {
+            settings.clouds = !settings.clouds;
+            println!("Clouds: {}", settings.clouds);
+            true
+        }
+        VirtualKeyCode::F8 => {
+            settings.tone_mapper = match settings.tone_mapper {
+                settings::ToneMapper::None => settings::ToneMapper::Reinhard,
+                settings::ToneMapper::Reinhard => settings::ToneMapper::Aces,
+                settings::ToneMapper::Aces => settings::ToneMapper::Agx,
+                settings::ToneMapper::Agx => settings::ToneMapper::None,
+            };
+            println!("Tone mapper: {:?}", settings.tone_mapper);
+            false
+        }
+        VirtualKeyCode::F9 => {
+            settings.auto_exposure = !settings.auto_exposure;
+            println!("Auto exposure: {}", settings.auto_exposure);
+            false
+        }
+        VirtualKeyCode::F10 => {
+            settings.debug_view = match settings.debug_view {
+                settings::DebugView::Off => settings::DebugView::Iterations,
+                settings::DebugView::Iterations => settings::DebugView::Depth,
+                settings::DebugView::Depth => settings::DebugView::Normals,
+                settings::DebugView::Normals => settings::DebugView::Materials,
+                settings::DebugView::Materials => settings::DebugView::StepHistogram,
+                settings::DebugView::StepHistogram => settings::DebugView::Off,
+            };
+            println!("Debug view: {:?}", settings.debug_view);
+            true
+        }
+        VirtualKeyCode::F11 => {
+            settings.quality = match settings.quality {
+                settings::Quality::Low => settings::Quality::Medium,
+                settings::Quality::Medium => settings::Quality::High,
+                settings::Quality::High => settings::Quality::Ultra,
+                settings::Quality::Ultra => settings::Quality::Low,
+            };
+            println!("Quality: {:?}", settings.quality);
+            true
+        }
+        VirtualKeyCode::F12 => {
+            settings.footprint_lod = !settings.footprint_lod;
+            println!("Footprint LOD: {}", settings.footprint_lod);
+            true
+        }
+        VirtualKeyCode::O => {
+            settings.over_relaxation = !settings.over_relaxation;
+            println!("Over-relaxation: {}", settings.over_relaxation);
+            false
+        }
+        VirtualKeyCode::Home | VirtualKeyCode::End => {
+            let step = if keycode == VirtualKeyCode::Home { 1.0 } else { -1.0 };
+            settings.relaxation_factor =
+                (settings.relaxation_factor + step * RELAXATION_STEP).clamp(1.0, 1.9);
+            println!("Relaxation factor: {:.1}", settings.relaxation_factor);
+            false
+        }
+        VirtualKeyCode::P => {
+            settings.cone_prepass = !settings.cone_prepass;
+            println!("Cone march pre-pass: {}", settings.cone_prepass);
+            false
+        }
+        VirtualKeyCode::R => {
+            settings.dynamic_resolution = !settings.dynamic_resolution;
+            println!("Dynamic resolution: {}", settings.dynamic_resolution);
+            false
+        }
+        VirtualKeyCode::Insert | VirtualKeyCode::Delete => {
+            // Overrides the dynamic resolution.
+            let step = if keycode == VirtualKeyCode::Insert { 1.0 } else { -1.0 };
+            settings.dynamic_resolution = false;
+            settings.render_scale = resolution::quantize_render_scale(
+                settings.render_scale + step * RENDER_SCALE_STEP,
+            );
+            println!("Render scale: {:.0}%", settings.render_scale * 100.0);
+            false
+        }
+        VirtualKeyCode::U => {
+            settings.upscale_filter = match settings.upscale_filter {
+                settings::UpscaleFilter::Bilinear => settings::UpscaleFilter::EdgeAware,
+                settings::UpscaleFilter::EdgeAware => settings::UpscaleFilter::Bilinear,
+            };
+            println!("Upscale filter: {:?}", settings.upscale_filter);
+            false
+        }
+        VirtualKeyCode::J => {
+            settings.taa = !settings.taa;
+            println!("TAA: {}", settings.taa);
+            false
+        }
+        VirtualKeyCode::G => {
+            let supersampling = &mut settings.supersampling;
+            supersampling.grid_size = supersampling.grid_size % MAX_SUPERSAMPLING_GRID + 1;
+            println!(
+                "Supersampling: {0}x{0} samples per pixel",
+                supersampling.grid_size,
+            );
+            false
+        }
+        VirtualKeyCode::H => {
+            settings.supersampling.pattern = match settings.supersampling.pattern {
+                settings::SamplePattern::Grid => settings::SamplePattern::Stratified,
+                settings::SamplePattern::Stratified => settings::SamplePattern::RotatedGrid,
+                settings::SamplePattern::RotatedGrid => settings::SamplePattern::Grid,
+            };
+            println!("Supersampling pattern: {:?}", settings.supersampling.pattern);
+            false
+        }
+        VirtualKeyCode::K => {
+            settings.supersampling.filter = match settings.supersampling.filter {
+                settings::ReconstructionFilter::Box => settings::ReconstructionFilter::Gaussian,
+                settings::ReconstructionFilter::Gaussian => settings::ReconstructionFilter::Mitchell,
+                settings::ReconstructionFilter::Mitchell => settings::ReconstructionFilter::Box,
+            };
+            println!("Supersampling filter: {:?}", settings.supersampling.filter);
+            false
+        }
+        VirtualKeyCode::Key9 | VirtualKeyCode::Key0 => {
+            let step = if keycode == VirtualKeyCode::Key0 { 1.0 } else { -1.0 };
+            settings.exposure += step * EXPOSURE_STEP;
+            println!("Exposure: {:+.1} EV", settings.exposure);
+            false
+        }
+        VirtualKeyCode::Key1
+        | VirtualKeyCode::Key2
+        | VirtualKeyCode::Key3
+        | VirtualKeyCode::Key4
+        | VirtualKeyCode::Key5
+        | VirtualKeyCode::Key6
+        | VirtualKeyCode::Key7
+        | VirtualKeyCode::Key8 => {
+            let index = keycode as usize - VirtualKeyCode::Key1 as usize;
+            let Some(pass) = settings.post.get_mut(index) else {
+                return false;
+            };
+            pass.enabled = !pass.enabled;
+            println!("Post pass {} ({:?}): {}", index + 1, pass.effect, pass.enabled);
+            false
+        }
+        VirtualKeyCode::Semicolon | VirtualKeyCode::Apostrophe => {
+            let step = if keycode == VirtualKeyCode::Apostrophe { 1.0 } else { -1.0 };
+            let Some(strength) = settings.post.iter_mut().find_map(|pass| match &mut pass.effect {
+                post::PostEffect::ColorGrading { strength, .. } => Some(strength),
+                _ => None,
+            }) else {
+                return false;
+            };
+            *strength = (*strength + step * COLOR_GRADING_STEP).clamp(0.0, 1.0);
+            println!("Color grading strength: {:.1}", strength);
+            false
+        }
+        VirtualKeyCode::LBracket | VirtualKeyCode::RBracket => {
+            let step = if keycode == VirtualKeyCode::RBracket { 1.0 } else { -1.0 };
+            settings.environment_rotation += step * ENVIRONMENT_ROTATION_STEP;
+            println!("Environment rotation: {:.0} degrees", settings.environment_rotation.to_degrees());
+            true
+        }
+        VirtualKeyCode::Comma | VirtualKeyCode::Period => {
+            let step = if keycode == VirtualKeyCode::Period { 1.0 } else { -1.0 };
+            settings.time_of_day = (settings.time_of_day + step * TIME_OF_DAY_STEP).rem_euclid(24.0);
+            println!("Time of day: {:.1} h", settings.time_of_day);
+            true
+        }
+        VirtualKeyCode::T => {
+            settings.sun_animation = !settings.sun_animation;
+            println!("Sun animation: {}", settings.sun_animation);
+            false
+        }
+        VirtualKeyCode::Minus => {
+            settings.environment_intensity /= ENVIRONMENT_INTENSITY_STEP;
+            println!("Environment intensity: {:.2}", settings.environment_intensity);
+            true
+        }
+        VirtualKeyCode::Equals => {
+            settings.environment_intensity *= ENVIRONMENT_INTENSITY_STEP;
+            println!("Environment intensity: {:.2}", settings.environment_intensity);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Moves the camera with WASD, Space and Left Control, and turns it with the
+/// arrow keys. Returns whether the camera moved.
+fn update_camera(
+    camera: &mut camera::Camera,
+    pressed_keys: &HashSet<VirtualKeyCode>,
+    delta_time: f32,
+) -> bool {
+    let axis = |positive, negative| {
+        pressed_keys.contains(&positive) as i32 as f32
+            - pressed_keys.contains(&negative) as i32 as f32
+    };
+
+    let mut speed = CAMERA_SPEED * delta_time;
+    if pressed_keys.contains(&VirtualKeyCode::LShift) {
+        speed *= 4.0;
+    }
+    let turn_speed = CAMERA_TURN_SPEED * delta_time;
+
+    let right = axis(VirtualKeyCode::D, VirtualKeyCode::A);
+    let up = axis(VirtualKeyCode::Space, VirtualKeyCode::LControl);
+    let forward = axis(VirtualKeyCode::W, VirtualKeyCode::S);
+    let yaw = axis(VirtualKeyCode::Right, VirtualKeyCode::Left);
+    let pitch = axis(VirtualKeyCode::Up, VirtualKeyCode::Down);
+
+    if right == 0.0 && up == 0.0 && forward == 0.0 && yaw == 0.0 && pitch == 0.0 {
+        return false;
+    }
+
+    camera.translate(right * speed, up * speed, forward * speed);
+    camera.rotate(yaw * turn_speed, pitch * turn_speed);
+    true
+}
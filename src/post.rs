@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+/// Fullscreen effect of the post-processing chain, applied to the HDR image
+/// before tone mapping.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostEffect {
+    /// Glow around bright areas, blurred through a pyramid of downsampled
+    /// images.
+    Bloom {
+        /// Brightness above which pixels start to bloom.
+        #[serde(default = "default_bloom_threshold")]
+        threshold: f32,
+        #[serde(default = "default_bloom_intensity")]
+        intensity: f32,
+        /// Spread of the upsampling filter, in texels of each pyramid level.
+        #[serde(default = "default_bloom_radius")]
+        radius: f32,
+    },
+    /// Darkens the corners of the image.
+    Vignette {
+        #[serde(default = "default_vignette_intensity")]
+        intensity: f32,
+    },
+    /// Splits the red and blue channels apart toward the edges of the image.
+    ChromaticAberration {
+        /// Offset of the channels at the edges, as a fraction of the image
+        /// size.
+        #[serde(default = "default_chromatic_aberration_strength")]
+        strength: f32,
+    },
+    /// Animated noise proportional to the brightness of each pixel.
+    FilmGrain {
+        #[serde(default = "default_film_grain_intensity")]
+        intensity: f32,
+    },
+    /// Unsharp mask over the four direct neighbors of each pixel.
+    Sharpen {
+        #[serde(default = "default_sharpen_strength")]
+        strength: f32,
+    },
+    /// Grading through a `.cube` LUT. LUTs are made for display images, so
+    /// it is applied after tone mapping wherever it is in the chain.
+    ColorGrading {
+        /// Path of the `.cube` file, relative to the scene file.
+        lut: PathBuf,
+        /// Blend from the ungraded (0) to the graded (1) image.
+        #[serde(default = "default_color_grading_strength")]
+        strength: f32,
+    },
+}
+
+/// An effect of the post-processing chain and whether it currently runs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostPass {
+    #[serde(flatten)]
+    pub effect: PostEffect,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_bloom_threshold() -> f32 {
+    1.0
+}
+
+fn default_bloom_intensity() -> f32 {
+    0.05
+}
+
+fn default_bloom_radius() -> f32 {
+    1.0
+}
+
+fn default_vignette_intensity() -> f32 {
+    0.25
+}
+
+fn default_chromatic_aberration_strength() -> f32 {
+    0.005
+}
+
+fn default_film_grain_intensity() -> f32 {
+    0.05
+}
+
+fn default_sharpen_strength() -> f32 {
+    0.3
+}
+
+fn default_color_grading_strength() -> f32 {
+    1.0
+}
+
+fn default_enabled() -> bool {
+    true
+}
@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+
+/// Number of most recent frames the rolling statistics cover.
+pub const STATS_WINDOW: usize = 300;
+
+/// Average and percentiles of a timing, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TimingSummary {
+    pub average: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+/// Rolling window of the most recent samples of a timing, in milliseconds.
+#[derive(Debug, Clone)]
+pub struct TimingStats {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl TimingStats {
+    /// Creates statistics over at most `capacity` samples, the oldest ones
+    /// being dropped first.
+    pub fn new(capacity: usize) -> Self {
+        TimingStats {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn add(&mut self, time: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(time);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the `p`th percentile of the samples, with `p` in [0, 100],
+    /// using the nearest rank. `None` without samples.
+    pub fn percentile(&self, p: f32) -> Option<f32> {
+        let mut sorted: Vec<f32> = self.samples.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        percentile(&sorted, p)
+    }
+
+    /// Returns the average, 95th and 99th percentiles of the samples, `None`
+    /// without samples.
+    pub fn summary(&self) -> Option<TimingSummary> {
+        let mut sorted: Vec<f32> = self.samples.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+
+        Some(TimingSummary {
+            average: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p95: percentile(&sorted, 95.0)?,
+            p99: percentile(&sorted, 99.0)?,
+        })
+    }
+}
+
+/// Nearest rank percentile of sorted samples.
+fn percentile(sorted: &[f32], p: f32) -> Option<f32> {
+    let rank = (p / 100.0 * sorted.len() as f32).ceil() as usize;
+    sorted.get(rank.clamp(1, sorted.len().max(1)) - 1).copied()
+}
+
+/// Rolling statistics of the named timings of a frame, such as the GPU passes
+/// and the CPU stages, listed in the order they were first recorded.
+#[derive(Debug, Clone)]
+pub struct FrameProfiler {
+    timings: Vec<(&'static str, TimingStats)>,
+    capacity: usize,
+}
+
+impl Default for FrameProfiler {
+    fn default() -> Self {
+        FrameProfiler::new(STATS_WINDOW)
+    }
+}
+
+impl FrameProfiler {
+    /// Creates a profiler keeping the last `capacity` samples of each timing.
+    pub fn new(capacity: usize) -> Self {
+        FrameProfiler {
+            timings: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Records a sample of the named timing, in milliseconds.
+    pub fn add(&mut self, name: &'static str, time: f32) {
+        match self.timings.iter_mut().find(|(timing, _)| *timing == name) {
+            Some((_, stats)) => stats.add(time),
+            None => {
+                let mut stats = TimingStats::new(self.capacity);
+                stats.add(time);
+                self.timings.push((name, stats));
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TimingStats> {
+        self.timings
+            .iter()
+            .find(|(timing, _)| *timing == name)
+            .map(|(_, stats)| stats)
+    }
+
+    /// Returns the summary of every timing recorded so far.
+    pub fn summaries(&self) -> Vec<(&'static str, TimingSummary)> {
+        self.timings
+            .iter()
+            .filter_map(|(name, stats)| Some((*name, stats.summary()?)))
+            .collect()
+    }
+
+    /// Discards all the samples, to start measuring anew.
+    pub fn clear(&mut self) {
+        self.timings.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_of(samples: impl IntoIterator<Item = u32>, capacity: usize) -> TimingStats {
+        let mut stats = TimingStats::new(capacity);
+        for sample in samples {
+            stats.add(sample as f32);
+        }
+        stats
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        // Added in reverse, the samples are sorted before ranking.
+        let stats = stats_of((1..=100).rev(), STATS_WINDOW);
+        assert_eq!(stats.percentile(0.0), Some(1.0));
+        assert_eq!(stats.percentile(50.0), Some(50.0));
+        assert_eq!(stats.percentile(95.0), Some(95.0));
+        assert_eq!(stats.percentile(99.0), Some(99.0));
+        assert_eq!(stats.percentile(100.0), Some(100.0));
+        assert_eq!(
+            stats.summary(),
+            Some(TimingSummary { average: 50.5, p95: 95.0, p99: 99.0 }),
+        );
+
+        let stats = stats_of([3, 1, 2], STATS_WINDOW);
+        assert_eq!(stats.percentile(50.0), Some(2.0));
+        assert_eq!(stats.percentile(99.0), Some(3.0));
+    }
+
+    #[test]
+    fn empty_window_has_no_statistics() {
+        let stats = TimingStats::new(STATS_WINDOW);
+        assert!(stats.is_empty());
+        assert_eq!(stats.percentile(50.0), None);
+        assert_eq!(stats.summary(), None);
+    }
+
+    #[test]
+    fn window_drops_the_oldest_samples() {
+        let stats = stats_of(1..=STATS_WINDOW as u32 + 10, STATS_WINDOW);
+        assert_eq!(stats.len(), STATS_WINDOW);
+        assert_eq!(stats.percentile(0.0), Some(11.0));
+        assert_eq!(stats.percentile(100.0), Some(STATS_WINDOW as f32 + 10.0));
+    }
+
+    #[test]
+    fn profiler_keeps_timings_in_first_recorded_order() {
+        let mut profiler = FrameProfiler::new(2);
+        profiler.add("GPU", 4.0);
+        profiler.add("CPU", 1.0);
+        profiler.add("GPU", 2.0);
+        profiler.add("GPU", 6.0);
+
+        let names: Vec<_> = profiler.summaries().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["GPU", "CPU"]);
+        assert_eq!(profiler.get("GPU").unwrap().len(), 2);
+        assert_eq!(profiler.summaries()[0].1.average, 4.0);
+        assert!(profiler.get("Present").is_none());
+
+        profiler.clear();
+        assert!(profiler.summaries().is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+/// Smallest fraction of the window's resolution rendered at.
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+/// Render scales are multiples of this step, so that small variations of the
+/// frame time don't recreate the render targets.
+pub const RENDER_SCALE_STEP: f32 = 0.05;
+/// Interval between two adjustments of the dynamic resolution, in seconds.
+const ADJUSTMENT_INTERVAL: f32 = 0.5;
+/// Fraction of the target frame time below it that the scale only goes up
+/// into, so that it doesn't oscillate between two steps around the target.
+const DEAD_BAND: f32 = 0.1;
+
+/// Rounds a render scale down to a multiple of `RENDER_SCALE_STEP`, between
+/// `MIN_RENDER_SCALE` and 1.
+pub fn quantize_render_scale(scale: f32) -> f32 {
+    // The epsilon keeps exact multiples from rounding down a step.
+    ((scale / RENDER_SCALE_STEP + 1e-3).floor() * RENDER_SCALE_STEP).clamp(MIN_RENDER_SCALE, 1.0)
+}
+
+/// Size of the render targets at the given fraction of the window's size.
+pub fn render_extent(window_extent: [u32; 2], scale: f32) -> [u32; 2] {
+    window_extent.map(|size| ((size as f32 * scale).round() as u32).max(1))
+}
+
+/// Picks the render scale reaching a target GPU frame time, from the frame
+/// times measured at the current scale.
+#[derive(Debug, Clone, Default)]
+pub struct DynamicResolution {
+    frame_time_sum: f32,
+    frame_count: u32,
+    /// Time of the last adjustment, in seconds.
+    last_adjustment: f32,
+}
+
+impl DynamicResolution {
+    /// Records the GPU time of a frame, in milliseconds.
+    pub fn add_frame_time(&mut self, frame_time: f32) {
+        self.frame_time_sum += frame_time;
+        self.frame_count += 1;
+    }
+
+    /// Returns the render scale to use from now on, adjusted at most once
+    /// per interval to the average of the frame times recorded at `scale`.
+    /// It goes down when the average is over the target, and up when a
+    /// larger scale would still be `DEAD_BAND` under it. `time` is in
+    /// seconds.
+    pub fn adjust(&mut self, scale: f32, target_frame_time: f32, time: f32) -> f32 {
+        if time - self.last_adjustment < ADJUSTMENT_INTERVAL || self.frame_count == 0 {
+            return scale;
+        }
+
+        let average = self.frame_time_sum / self.frame_count as f32;
+        self.frame_time_sum = 0.0;
+        self.frame_count = 0;
+        self.last_adjustment = time;
+
+        // The frame time is roughly proportional to the number of pixels,
+        // the square of the scale. Rounding down leaves some headroom.
+        if average > target_frame_time {
+            quantize_render_scale(scale * (target_frame_time / average).sqrt()).min(scale)
+        } else {
+            let target = target_frame_time * (1.0 - DEAD_BAND);
+            quantize_render_scale(scale * (target / average).sqrt()).max(scale)
+        }
+    }
+
+    /// Discards the frame times recorded so far, which no longer match the
+    /// render scale.
+    pub fn reset(&mut self) {
+        self.frame_time_sum = 0.0;
+        self.frame_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: f32 = 16.0;
+
+    /// Adjusts the scale after one interval of frames taking `frame_time`.
+    fn adjust_after(dynamic: &mut DynamicResolution, scale: f32, frame_time: f32, time: f32) -> f32 {
+        dynamic.add_frame_time(frame_time);
+        dynamic.adjust(scale, TARGET, time)
+    }
+
+    #[test]
+    fn quantizes_to_steps_within_bounds() {
+        assert_eq!(quantize_render_scale(1.0), 1.0);
+        assert_eq!(quantize_render_scale(1.3), 1.0);
+        assert_eq!(quantize_render_scale(0.25), MIN_RENDER_SCALE);
+        assert_eq!(quantize_render_scale(0.1), MIN_RENDER_SCALE);
+        assert_eq!(quantize_render_scale(-1.0), MIN_RENDER_SCALE);
+        assert!((quantize_render_scale(0.5) - 0.5).abs() < 1e-6);
+        assert!((quantize_render_scale(0.74) - 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn render_extent_is_never_empty() {
+        assert_eq!(render_extent([1280, 720], 0.5), [640, 360]);
+        assert_eq!(render_extent([1, 1], MIN_RENDER_SCALE), [1, 1]);
+    }
+
+    #[test]
+    fn waits_for_the_interval_and_frames() {
+        let mut dynamic = DynamicResolution::default();
+        assert_eq!(dynamic.adjust(1.0, TARGET, 1.0), 1.0);
+        assert_eq!(adjust_after(&mut dynamic, 1.0, 4.0 * TARGET, 0.1), 1.0);
+    }
+
+    #[test]
+    fn scales_down_over_the_target() {
+        let mut dynamic = DynamicResolution::default();
+        // A quarter of the pixels takes a quarter of the time.
+        assert!((adjust_after(&mut dynamic, 1.0, 4.0 * TARGET, 1.0) - 0.5).abs() < 1e-6);
+
+        let mut dynamic = DynamicResolution::default();
+        assert!((adjust_after(&mut dynamic, 1.0, 1.02 * TARGET, 1.0) - 0.95).abs() < 1e-6);
+
+        let mut dynamic = DynamicResolution::default();
+        assert_eq!(adjust_after(&mut dynamic, 0.3, 100.0 * TARGET, 1.0), MIN_RENDER_SCALE);
+    }
+
+    #[test]
+    fn scales_up_under_the_dead_band() {
+        let mut dynamic = DynamicResolution::default();
+        assert!((adjust_after(&mut dynamic, 0.5, TARGET / 4.0, 1.0) - 0.9).abs() < 1e-6);
+
+        let mut dynamic = DynamicResolution::default();
+        assert_eq!(adjust_after(&mut dynamic, 0.5, 0.95 * TARGET, 1.0), 0.5);
+
+        let mut dynamic = DynamicResolution::default();
+        assert_eq!(adjust_after(&mut dynamic, 1.0, TARGET / 4.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn settles_between_two_steps() {
+        // Frame times proportional to the pixels, the target falling between
+        // the 0.7 and 0.75 steps.
+        let cost = |scale: f32| TARGET * (scale / 0.72).powi(2);
+        let mut dynamic = DynamicResolution::default();
+        let mut scale = 1.0;
+        let mut changes = 0;
+        for i in 1..=40 {
+            let adjusted = adjust_after(&mut dynamic, scale, cost(scale), i as f32);
+            if adjusted != scale {
+                changes += 1;
+                dynamic.reset();
+            }
+            scale = adjusted;
+        }
+        assert!(changes <= 2, "{changes} changes");
+        assert!(cost(scale) <= TARGET);
+    }
+}
@@ -0,0 +1,578 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::atmosphere::Atmosphere;
+use crate::camera::Camera;
+use crate::clouds::Clouds;
+use crate::post::{PostEffect, PostPass};
+
+/// Maximum number of lights the fragment shader can evaluate. Must match
+/// `MAX_LIGHTS` in `ray-marcher.glsl`.
+pub const MAX_LIGHTS: usize = 8;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Light {
+    Directional {
+        direction: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        #[serde(default = "default_light_intensity")]
+        intensity: f32,
+    },
+    Point {
+        position: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        #[serde(default = "default_light_intensity")]
+        intensity: f32,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Material {
+    pub name: String,
+    #[serde(default = "default_albedo")]
+    pub albedo: [f32; 3],
+    #[serde(default = "default_roughness")]
+    pub roughness: f32,
+    #[serde(default)]
+    pub metallic: f32,
+    #[serde(default)]
+    pub emissive: [f32; 3],
+    #[serde(default = "default_specular")]
+    pub specular: f32,
+    /// Fraction of the light mirrored by the surface.
+    #[serde(default)]
+    pub reflectivity: f32,
+    /// Fraction of the light refracted through the object.
+    #[serde(default)]
+    pub transmission: f32,
+    #[serde(default = "default_ior")]
+    pub ior: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Shape {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        /// Half extents of the box along each axis.
+        size: [f32; 3],
+        #[serde(default)]
+        rounding: f32,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Plane {
+        normal: [f32; 3],
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Node {
+    pub shape: Shape,
+    #[serde(default)]
+    pub position: [f32; 3],
+    /// Cell size of the infinite repetition along each axis, `0.0` disables
+    /// the repetition on that axis.
+    #[serde(default)]
+    pub repeat: [f32; 3],
+    /// Name of the node's material, the first material of the scene is used
+    /// when omitted.
+    #[serde(default)]
+    pub material: Option<String>,
+}
+
+/// Environment surrounding the scene, seen on misses and used for image-based
+/// lighting. When no map is given the sky is either the atmosphere, or the
+/// procedural sky colors.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Environment {
+    /// Equirectangular `.hdr` or `.exr` image, relative to the scene file.
+    #[serde(default)]
+    pub map: Option<PathBuf>,
+    /// Rotation of the map around the vertical axis, in radians.
+    #[serde(default)]
+    pub rotation: f32,
+    /// Physical sky, also lighting the scene with its sun.
+    #[serde(default)]
+    pub atmosphere: Option<Atmosphere>,
+    #[serde(default = "default_sky_zenith")]
+    pub zenith: [f32; 3],
+    #[serde(default = "default_sky_horizon")]
+    pub horizon: [f32; 3],
+    #[serde(default = "default_sky_ground")]
+    pub ground: [f32; 3],
+    #[serde(default = "default_environment_intensity")]
+    pub intensity: f32,
+}
+
+/// Exponential height fog, its density decaying above `height`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fog {
+    pub density: f32,
+    /// Rate at which the density decays with height, `0.0` for uniform fog.
+    #[serde(default)]
+    pub falloff: f32,
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default = "default_medium_albedo")]
+    pub albedo: [f32; 3],
+    /// Henyey-Greenstein asymmetry, positive values scatter forward.
+    #[serde(default)]
+    pub anisotropy: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum VolumeDensity {
+    Constant,
+    /// Fractal value noise.
+    Noise {
+        #[serde(default = "default_noise_scale")]
+        scale: f32,
+        #[serde(default = "default_noise_octaves")]
+        octaves: u32,
+    },
+    /// Raw 8-bit density grid of the given resolution, stretched over the
+    /// volume's box. Paths are relative to the scene file.
+    Texture {
+        path: PathBuf,
+        resolution: [u32; 3],
+    },
+}
+
+/// Box of participating medium whose density is `density` scaled by the
+/// density field.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Volume {
+    pub field: VolumeDensity,
+    pub density: f32,
+    #[serde(default)]
+    pub position: [f32; 3],
+    /// Half extents of the box along each axis.
+    pub size: [f32; 3],
+    #[serde(default = "default_medium_albedo")]
+    pub albedo: [f32; 3],
+    #[serde(default)]
+    pub anisotropy: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scene {
+    #[serde(default)]
+    pub camera: Camera,
+    /// Constant ambient term of the Blinn-Phong shading model.
+    #[serde(default = "default_ambient")]
+    pub ambient: [f32; 3],
+    #[serde(default)]
+    pub environment: Environment,
+    #[serde(default)]
+    pub lights: Vec<Light>,
+    #[serde(default = "default_materials")]
+    pub materials: Vec<Material>,
+    #[serde(default = "default_nodes")]
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub fog: Option<Fog>,
+    #[serde(default)]
+    pub clouds: Option<Clouds>,
+    #[serde(default)]
+    pub volumes: Vec<Volume>,
+    /// Post-processing chain, applied in order.
+    #[serde(default)]
+    pub post: Vec<PostPass>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    TooManyLights(usize),
+    NoMaterials,
+    NoNodes,
+    UnknownMaterial(String),
+    /// Material with an index of refraction of zero or less.
+    InvalidIor(String),
+    /// Index of a node whose plane normal is zero.
+    ZeroPlaneNormal(usize),
+    /// Index of a node whose shape has a radius or size of zero or less.
+    InvalidShapeSize(usize),
+    /// Index of a volume with a size or noise scale of zero or less.
+    InvalidVolume(usize),
+    TooManyVolumeTextures,
+    EmptyCloudLayer,
+    /// Cloud noise scale of zero or less.
+    InvalidCloudScale,
+    TooManyLuts,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "could not read scene file: {e}"),
+            SceneError::Parse(e) => write!(f, "could not parse scene file: {e}"),
+            SceneError::TooManyLights(count) => write!(
+                f,
+                "scene has {count} lights, at most {MAX_LIGHTS} are supported",
+            ),
+            SceneError::NoMaterials => write!(f, "scene must define at least one material"),
+            SceneError::NoNodes => write!(f, "scene must contain at least one node"),
+            SceneError::UnknownMaterial(name) => write!(f, "unknown material \"{name}\""),
+            SceneError::InvalidIor(name) => {
+                write!(f, "material \"{name}\" must have a positive index of refraction")
+            }
+            SceneError::ZeroPlaneNormal(index) => {
+                write!(f, "plane of node {index} must have a non-zero normal")
+            }
+            SceneError::InvalidShapeSize(index) => {
+                write!(f, "shape of node {index} must have a positive size")
+            }
+            SceneError::InvalidVolume(index) => {
+                write!(f, "volume {index} must have a positive size and noise scale")
+            }
+            SceneError::TooManyVolumeTextures => {
+                write!(f, "at most one volume can use a texture density field")
+            }
+            SceneError::EmptyCloudLayer => write!(f, "cloud layer top must be above its bottom"),
+            SceneError::InvalidCloudScale => write!(f, "cloud noise scales must be positive"),
+            SceneError::TooManyLuts => write!(f, "at most one post pass can use color grading"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let source = fs::read_to_string(&path).map_err(SceneError::Io)?;
+        let mut scene: Scene = serde_json::from_str(&source).map_err(SceneError::Parse)?;
+        scene.validate()?;
+
+        if let Some(dir) = path.as_ref().parent() {
+            if let Some(map) = &mut scene.environment.map {
+                *map = dir.join(&*map);
+            }
+            for volume in &mut scene.volumes {
+                if let VolumeDensity::Texture { path, .. } = &mut volume.field {
+                    *path = dir.join(&*path);
+                }
+            }
+            for pass in &mut scene.post {
+                if let PostEffect::ColorGrading { lut, .. } = &mut pass.effect {
+                    *lut = dir.join(&*lut);
+                }
+            }
+        }
+
+        Ok(scene)
+    }
+
+    fn validate(&self) -> Result<(), SceneError> {
+        if self.lights.len() > MAX_LIGHTS {
+            return Err(SceneError::TooManyLights(self.lights.len()));
+        }
+        if self.materials.is_empty() {
+            return Err(SceneError::NoMaterials);
+        }
+        if self.nodes.is_empty() {
+            return Err(SceneError::NoNodes);
+        }
+
+        if let Some(material) = self.materials.iter().find(|material| material.ior <= 0.0) {
+            return Err(SceneError::InvalidIor(material.name.clone()));
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let sizes = match node.shape {
+                Shape::Sphere { radius } => vec![radius],
+                Shape::Box { size, .. } => size.to_vec(),
+                Shape::Torus { major_radius, minor_radius } => vec![major_radius, minor_radius],
+                Shape::Plane { normal } => {
+                    if normal == [0.0; 3] {
+                        return Err(SceneError::ZeroPlaneNormal(index));
+                    }
+                    Vec::new()
+                }
+            };
+            if sizes.iter().any(|&size| size <= 0.0) {
+                return Err(SceneError::InvalidShapeSize(index));
+            }
+
+            if let Some(name) = &node.material {
+                self.material_index(Some(name.as_str()))
+                    .ok_or_else(|| SceneError::UnknownMaterial(name.clone()))?;
+            }
+        }
+
+        for (index, volume) in self.volumes.iter().enumerate() {
+            let noise_scale = match volume.field {
+                VolumeDensity::Noise { scale, .. } => scale,
+                _ => 1.0,
+            };
+            if volume.size.iter().any(|&size| size <= 0.0) || noise_scale <= 0.0 {
+                return Err(SceneError::InvalidVolume(index));
+            }
+        }
+
+        let texture_count = self.volumes
+            .iter()
+            .filter(|volume| matches!(volume.field, VolumeDensity::Texture { .. }))
+            .count();
+        if texture_count > 1 {
+            return Err(SceneError::TooManyVolumeTextures);
+        }
+
+        if let Some(clouds) = &self.clouds {
+            if clouds.top <= clouds.bottom {
+                return Err(SceneError::EmptyCloudLayer);
+            }
+            if clouds.scale <= 0.0 || clouds.detail_scale <= 0.0 {
+                return Err(SceneError::InvalidCloudScale);
+            }
+        }
+
+        let lut_count = self.post
+            .iter()
+            .filter(|pass| matches!(pass.effect, PostEffect::ColorGrading { .. }))
+            .count();
+        if lut_count > 1 {
+            return Err(SceneError::TooManyLuts);
+        }
+
+        Ok(())
+    }
+
+    /// Index of the named material in the material table, `None` picks the
+    /// first material.
+    pub fn material_index(&self, name: Option<&str>) -> Option<usize> {
+        match name {
+            Some(name) => self.materials.iter().position(|m| m.name == name),
+            None => Some(0),
+        }
+    }
+
+    /// Path and resolution of the volume texture, used by at most one volume.
+    pub fn volume_texture(&self) -> Option<(&Path, [u32; 3])> {
+        self.volumes.iter().find_map(|volume| match &volume.field {
+            VolumeDensity::Texture { path, resolution } => Some((path.as_path(), *resolution)),
+            _ => None,
+        })
+    }
+
+    /// Path of the color grading LUT, used by at most one post pass.
+    pub fn color_grading_lut(&self) -> Option<&Path> {
+        self.post.iter().find_map(|pass| match &pass.effect {
+            PostEffect::ColorGrading { lut, .. } => Some(lut.as_path()),
+            _ => None,
+        })
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            camera: Camera::default(),
+            ambient: default_ambient(),
+            environment: Environment::default(),
+            lights: vec![
+                Light::Directional {
+                    direction: [-0.4, -1.0, 0.6],
+                    color: [1.0, 0.95, 0.85],
+                    intensity: 1.0,
+                },
+                Light::Point {
+                    position: [0.0, 0.0, 4.0],
+                    color: [0.4, 0.6, 1.0],
+                    intensity: 8.0,
+                },
+            ],
+            materials: default_materials(),
+            nodes: default_nodes(),
+            fog: None,
+            clouds: None,
+            volumes: Vec::new(),
+            post: Vec::new(),
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            map: None,
+            rotation: 0.0,
+            atmosphere: None,
+            zenith: default_sky_zenith(),
+            horizon: default_sky_horizon(),
+            ground: default_sky_ground(),
+            intensity: default_environment_intensity(),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::from("default"),
+            albedo: default_albedo(),
+            roughness: default_roughness(),
+            metallic: 0.0,
+            emissive: [0.0; 3],
+            specular: default_specular(),
+            reflectivity: 0.0,
+            transmission: 0.0,
+            ior: default_ior(),
+        }
+    }
+}
+
+fn default_ambient() -> [f32; 3] {
+    [0.03, 0.03, 0.04]
+}
+
+fn default_sky_zenith() -> [f32; 3] {
+    [0.15, 0.3, 0.6]
+}
+
+fn default_sky_horizon() -> [f32; 3] {
+    [0.6, 0.65, 0.7]
+}
+
+fn default_sky_ground() -> [f32; 3] {
+    [0.2, 0.18, 0.15]
+}
+
+fn default_environment_intensity() -> f32 {
+    0.5
+}
+
+fn default_materials() -> Vec<Material> {
+    vec![Material::default()]
+}
+
+/// An infinite grid of spheres.
+fn default_nodes() -> Vec<Node> {
+    vec![Node {
+        shape: Shape::Sphere { radius: 0.5 },
+        position: [2.5, 2.5, 2.5],
+        repeat: [5.0, 5.0, 5.0],
+        material: None,
+    }]
+}
+
+fn default_albedo() -> [f32; 3] {
+    [0.8, 0.8, 0.8]
+}
+
+fn default_roughness() -> f32 {
+    0.5
+}
+
+fn default_specular() -> f32 {
+    0.5
+}
+
+fn default_ior() -> f32 {
+    1.5
+}
+
+fn default_light_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_light_intensity() -> f32 {
+    1.0
+}
+
+fn default_medium_albedo() -> [f32; 3] {
+    [0.9, 0.9, 0.9]
+}
+
+fn default_noise_scale() -> f32 {
+    1.0
+}
+
+fn default_noise_octaves() -> u32 {
+    4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_scene_is_valid() {
+        assert!(Scene::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_non_positive_ior() {
+        for ior in [0.0, -1.5] {
+            let mut scene = Scene::default();
+            scene.materials[0].ior = ior;
+            assert!(matches!(
+                scene.validate(),
+                Err(SceneError::InvalidIor(name)) if name == "default"
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_shapes_and_volumes() {
+        let with_shape = |shape| {
+            let mut scene = Scene::default();
+            scene.nodes[0].shape = shape;
+            scene.validate()
+        };
+        assert!(matches!(
+            with_shape(Shape::Plane { normal: [0.0; 3] }),
+            Err(SceneError::ZeroPlaneNormal(0)),
+        ));
+        assert!(with_shape(Shape::Plane { normal: [0.0, 1.0, 0.0] }).is_ok());
+        assert!(matches!(
+            with_shape(Shape::Sphere { radius: 0.0 }),
+            Err(SceneError::InvalidShapeSize(0)),
+        ));
+        assert!(matches!(
+            with_shape(Shape::Box { size: [1.0, -1.0, 1.0], rounding: 0.0 }),
+            Err(SceneError::InvalidShapeSize(0)),
+        ));
+
+        let with_volume = |size, field| {
+            let mut scene = Scene::default();
+            scene.volumes.push(Volume {
+                field,
+                density: 1.0,
+                position: [0.0; 3],
+                size,
+                albedo: default_medium_albedo(),
+                anisotropy: 0.0,
+            });
+            scene.validate()
+        };
+        assert!(with_volume([1.0; 3], VolumeDensity::Constant).is_ok());
+        assert!(matches!(
+            with_volume([1.0, 0.0, 1.0], VolumeDensity::Constant),
+            Err(SceneError::InvalidVolume(0)),
+        ));
+        assert!(matches!(
+            with_volume([1.0; 3], VolumeDensity::Noise { scale: 0.0, octaves: 4 }),
+            Err(SceneError::InvalidVolume(0)),
+        ));
+
+        let mut scene = Scene::default();
+        let mut clouds: Clouds = serde_json::from_str("{}").unwrap();
+        clouds.detail_scale = -1.0;
+        scene.clouds = Some(clouds);
+        assert!(matches!(scene.validate(), Err(SceneError::InvalidCloudScale)));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::post::PostPass;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadingModel {
+    BlinnPhong,
+    /// Cook-Torrance GGX with image-based ambient lighting.
+    Pbr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Sphere tracing with direct lighting, reflections and refractions.
+    Direct,
+    /// Progressive Monte Carlo path tracing, accumulated across frames.
+    PathTraced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapper {
+    /// No curve, colors above 1 are clipped.
+    None,
+    Reinhard,
+    /// Stephen Hill's fit of the ACES filmic transforms.
+    Aces,
+    /// Minimal AgX, which desaturates bright colors instead of skewing them.
+    Agx,
+}
+
+/// Filter upscaling the rendered image to the window when it is rendered at a
+/// lower resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    Bilinear,
+    /// Bilinear weights falling off across luminance edges, which keeps the
+    /// silhouettes sharp.
+    EdgeAware,
+}
+
+/// Preset of the march limits, baked into the ray marching pipeline as
+/// specialization constants. Changing it recreates the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl Quality {
+    /// Maximum number of steps of a march.
+    pub fn max_iterations(self) -> i32 {
+        match self {
+            Quality::Low => 128,
+            Quality::Medium => 256,
+            Quality::High => 512,
+            Quality::Ultra => 1024,
+        }
+    }
+
+    /// Distance at which a march gives up.
+    pub fn max_distance(self) -> f32 {
+        match self {
+            Quality::Low => 50.0,
+            Quality::Medium => 75.0,
+            Quality::High => 100.0,
+            Quality::Ultra => 200.0,
+        }
+    }
+
+    /// Distance to a surface at which a march counts as a hit.
+    pub fn hit_distance(self) -> f32 {
+        match self {
+            Quality::Low => 0.004,
+            Quality::Medium => 0.002,
+            Quality::High => 0.001,
+            Quality::Ultra => 0.0005,
+        }
+    }
+}
+
+/// Visualization of the ray marching replacing the shading, for diagnosing
+/// performance and artifacts. Rays that run out of iterations without hitting
+/// anything are highlighted in every view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    Off,
+    /// Heatmap of the iterations each primary ray took, up to the maximum.
+    Iterations,
+    /// Distance to the first hit.
+    Depth,
+    Normals,
+    /// Random color per material.
+    Materials,
+    /// Iteration heatmap overlaid with a histogram of the step sizes.
+    StepHistogram,
+}
+
+/// Edge-avoiding a-trous wavelet filter applied to the path traced image.
+/// The `*_phi` parameters control how quickly the filter stops at
+/// differences in the color, normal, depth and albedo buffers, higher values
+/// blur more.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenoiserSettings {
+    pub enabled: bool,
+    /// Number of filter passes, each one doubling the filter's footprint.
+    pub iterations: u32,
+    pub color_phi: f32,
+    pub normal_phi: f32,
+    pub depth_phi: f32,
+    pub albedo_phi: f32,
+}
+
+impl Default for DenoiserSettings {
+    fn default() -> Self {
+        DenoiserSettings {
+            enabled: true,
+            iterations: 5,
+            color_phi: 0.5,
+            normal_phi: 0.1,
+            depth_phi: 0.5,
+            albedo_phi: 0.05,
+        }
+    }
+}
+
+/// Placement of the supersamples over the reconstruction filter's footprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplePattern {
+    /// Centers of the cells of an N×N grid.
+    Grid,
+    /// One random sample per cell of the grid, different every frame.
+    Stratified,
+    /// Grid with its rows and columns offset so that no two samples share a
+    /// row or column, like a rotated grid.
+    RotatedGrid,
+}
+
+/// Filter weighting the supersamples by their offset from the pixel's center.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconstructionFilter {
+    /// Equal weights over the pixel.
+    Box,
+    /// Gaussian of 0.5 pixel standard deviation, over a 1.5 pixels radius.
+    Gaussian,
+    /// Mitchell-Netravali with B = C = 1/3, over a 2 pixels radius.
+    Mitchell,
+}
+
+/// Supersampling anti-aliasing of the direct rendering, tracing several rays
+/// per pixel in the ray marcher itself. Meant for final quality stills rather
+/// than real time, and independent of the TAA.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SupersamplingSettings {
+    /// Samples per pixel along each axis, 1 disabling the supersampling.
+    pub grid_size: u32,
+    pub pattern: SamplePattern,
+    pub filter: ReconstructionFilter,
+}
+
+impl Default for SupersamplingSettings {
+    fn default() -> Self {
+        SupersamplingSettings {
+            grid_size: 1,
+            pattern: SamplePattern::RotatedGrid,
+            filter: ReconstructionFilter::Mitchell,
+        }
+    }
+}
+
+/// Renderer options that can change while the application is running. They
+/// are uploaded to the fragment shader every frame.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub render_mode: RenderMode,
+    pub shading_model: ShadingModel,
+    pub shadows: bool,
+    /// Penumbra factor of the soft shadows, higher values give harder shadows.
+    pub shadow_softness: f32,
+    pub ambient_occlusion: bool,
+    pub ao_samples: u32,
+    /// Distance between two ambient occlusion samples along the normal.
+    pub ao_step_size: f32,
+    pub ao_strength: f32,
+    /// Maximum number of reflection and refraction bounces per pixel.
+    pub max_bounces: u32,
+    pub denoiser: DenoiserSettings,
+    /// Rotation of the environment around the vertical axis, in radians.
+    pub environment_rotation: f32,
+    pub environment_intensity: f32,
+    /// Local solar time of the atmosphere's sun, in hours.
+    pub time_of_day: f32,
+    pub sun_animation: bool,
+    /// Whether the fog and volumes are marched.
+    pub volumetrics: bool,
+    /// Number of samples along each ray through participating media.
+    pub volume_steps: u32,
+    pub clouds: bool,
+    /// Number of samples along each ray through the cloud layer.
+    pub cloud_steps: u32,
+    /// Number of samples toward the light for the clouds' self-shadowing.
+    pub cloud_light_steps: u32,
+    /// Whether the hit threshold grows with the pixel's footprint and noise
+    /// detail finer than it is skipped, instead of a fixed threshold and
+    /// full detail at every distance.
+    pub footprint_lod: bool,
+    /// Whether the march takes over-relaxed steps, falling back to plain
+    /// sphere tracing where they overshoot.
+    pub over_relaxation: bool,
+    /// Factor the distance is scaled by in over-relaxed steps, from 1 to 1.9.
+    pub relaxation_factor: f32,
+    /// Whether primary rays start at the distances found by cone marching
+    /// the scene at lower resolutions first.
+    pub cone_prepass: bool,
+    /// Whether the render scale adapts to the GPU frame time to reach
+    /// `target_frame_time`.
+    pub dynamic_resolution: bool,
+    /// GPU time per frame the dynamic resolution aims for, in milliseconds.
+    pub target_frame_time: f32,
+    /// Fraction of the window's resolution rendered at when the dynamic
+    /// resolution is off.
+    pub render_scale: f32,
+    pub upscale_filter: UpscaleFilter,
+    /// Whether the primary rays are jittered every frame and resolved with
+    /// the reprojected history, in direct rendering only.
+    pub taa: bool,
+    pub supersampling: SupersamplingSettings,
+    pub tone_mapper: ToneMapper,
+    /// Whether the exposure follows the luminance histogram of the image.
+    pub auto_exposure: bool,
+    /// Exposure in stops, a compensation on top of the auto exposure when
+    /// enabled.
+    pub exposure: f32,
+    /// Rate at which the auto exposure adapts to the scene, per second.
+    pub exposure_adaptation: f32,
+    /// Post-processing chain, the disabled passes being skipped.
+    pub post: Vec<PostPass>,
+    pub debug_view: DebugView,
+    /// Whether the iterations of the primary rays are counted outside the
+    /// debug views, at the cost of marching them twice more.
+    pub march_stats: bool,
+    pub quality: Quality,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            render_mode: RenderMode::Direct,
+            shading_model: ShadingModel::BlinnPhong,
+            shadows: true,
+            shadow_softness: 16.0,
+            ambient_occlusion: true,
+            ao_samples: 5,
+            ao_step_size: 0.05,
+            ao_strength: 3.0,
+            max_bounces: 4,
+            denoiser: DenoiserSettings::default(),
+            environment_rotation: 0.0,
+            environment_intensity: 1.0,
+            time_of_day: 12.0,
+            sun_animation: false,
+            volumetrics: true,
+            volume_steps: 32,
+            clouds: true,
+            cloud_steps: 64,
+            cloud_light_steps: 6,
+            footprint_lod: false,
+            over_relaxation: false,
+            relaxation_factor: 1.5,
+            cone_prepass: false,
+            dynamic_resolution: false,
+            target_frame_time: 1000.0 / 60.0,
+            render_scale: 1.0,
+            upscale_filter: UpscaleFilter::EdgeAware,
+            taa: false,
+            supersampling: SupersamplingSettings::default(),
+            tone_mapper: ToneMapper::None,
+            auto_exposure: false,
+            exposure: 0.0,
+            exposure_adaptation: 1.5,
+            post: Vec::new(),
+            debug_view: DebugView::Off,
+            march_stats: false,
+            quality: Quality::High,
+        }
+    }
+}
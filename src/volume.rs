@@ -0,0 +1,43 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Density grid of a volume, one byte per voxel with X varying fastest.
+#[derive(Debug, Clone)]
+pub struct VolumeTexture {
+    pub resolution: [u32; 3],
+    pub voxels: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum VolumeError {
+    Io(std::io::Error),
+    SizeMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VolumeError::Io(e) => write!(f, "could not read volume texture: {e}"),
+            VolumeError::SizeMismatch { expected, actual } => write!(
+                f,
+                "volume texture has {actual} bytes, its resolution requires {expected}",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VolumeError {}
+
+impl VolumeTexture {
+    pub fn load<P: AsRef<Path>>(path: P, resolution: [u32; 3]) -> Result<VolumeTexture, VolumeError> {
+        let voxels = fs::read(path).map_err(VolumeError::Io)?;
+
+        let expected = resolution.iter().map(|&r| r as usize).product();
+        if voxels.len() != expected {
+            return Err(VolumeError::SizeMismatch { expected, actual: voxels.len() });
+        }
+
+        Ok(VolumeTexture { resolution, voxels })
+    }
+}
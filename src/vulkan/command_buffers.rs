@@ -1,56 +1,128 @@
 use std::sync::Arc;
 
 use vulkano::buffer::Subbuffer;
-use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassBeginInfo, SubpassContents, SubpassEndInfo};
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract, RenderPassBeginInfo, SubpassBeginInfo, SubpassContents, SubpassEndInfo};
+use vulkano::descriptor_set::{DescriptorSetsCollection, PersistentDescriptorSet};
 use vulkano::device::Queue;
-use vulkano::pipeline::{GraphicsPipeline, PipelineLayout};
-use vulkano::render_pass::Framebuffer;
+use vulkano::format::ClearValue;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{AttachmentLoadOp, Framebuffer};
+use vulkano::sync::GpuFuture;
 
-use super::shaders;
 use super::vertex::Vertex;
 
-pub fn get_command_buffers(
-    command_buffer_allocator: &StandardCommandBufferAllocator,
-    queue: &Arc<Queue>,
-    pipeline_layout: &Arc<PipelineLayout>,
+/// Begins a render pass that draws the fullscreen quad with the given
+/// pipeline over the whole framebuffer. Push constants can be recorded before
+/// ending it with `end_fullscreen_pass`.
+pub fn begin_fullscreen_pass(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    framebuffer: &Arc<Framebuffer>,
     pipeline: &Arc<GraphicsPipeline>,
-    framebuffers: &Vec<Arc<Framebuffer>>,
     vertex_buffer: &Subbuffer<[Vertex]>,
-    push_constants: shaders::fs::constants,
-) -> Vec<Arc<PrimaryAutoCommandBuffer>>{
-    framebuffers
+    descriptor_set: &Arc<PersistentDescriptorSet>,
+) {
+    let clear_values = framebuffer
+        .render_pass()
+        .attachments()
         .iter()
-        .map(move |framebuffer| {
-            let mut builder = AutoCommandBufferBuilder::primary(
-                command_buffer_allocator,
-                queue.queue_family_index(),
-                CommandBufferUsage::MultipleSubmit,
-            ).unwrap();
-
-            builder
-                .begin_render_pass(
-                    RenderPassBeginInfo {
-                        clear_values: vec![Some([0.1, 0.1, 0.1, 1.0].into())],
-                        ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                    },
-                    SubpassBeginInfo {
-                        contents: SubpassContents::Inline,
-                        ..Default::default()
-                    },
-                ).unwrap()
-                .bind_pipeline_graphics(pipeline.clone())
-                .unwrap()
-                .bind_vertex_buffers(0, vertex_buffer.clone())
-                .unwrap()
-                .push_constants(pipeline_layout.clone(), 0, push_constants)
-                .unwrap()
-                .draw(vertex_buffer.len() as u32, 1, 0, 0)
-                .unwrap()
-                .end_render_pass(SubpassEndInfo::default())
-                .unwrap();
-
-            builder.build().unwrap()
+        .map(|attachment| match attachment.load_op {
+            AttachmentLoadOp::Clear => Some(ClearValue::Float([0.0, 0.0, 0.0, 0.0])),
+            _ => None,
         })
-        .collect()
+        .collect();
+
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values,
+                ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+            },
+            SubpassBeginInfo {
+                contents: SubpassContents::Inline,
+                ..Default::default()
+            },
+        ).unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .unwrap()
+        .set_viewport(
+            0,
+            [Viewport {
+                offset: [0.0, 0.0],
+                extent: framebuffer.extent().map(|size| size as f32),
+                depth_range: 0.0..=1.0,
+            }]
+            .into_iter()
+            .collect(),
+        )
+        .unwrap()
+        .bind_vertex_buffers(0, vertex_buffer.clone())
+        .unwrap()
+        .bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            descriptor_set.clone(),
+        )
+        .unwrap();
+}
+
+pub fn end_fullscreen_pass(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    vertex_buffer: &Subbuffer<[Vertex]>,
+) {
+    builder
+        .draw(vertex_buffer.len() as u32, 1, 0, 0)
+        .unwrap()
+        .end_render_pass(SubpassEndInfo::default())
+        .unwrap();
+}
+
+/// Dispatches the given number of workgroups of a compute pipeline, with the
+/// descriptor sets bound from set 0.
+pub fn dispatch(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipeline: &Arc<ComputePipeline>,
+    descriptor_sets: impl DescriptorSetsCollection,
+    group_counts: [u32; 3],
+) {
+    builder
+        .bind_pipeline_compute(pipeline.clone())
+        .unwrap()
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            pipeline.layout().clone(),
+            0,
+            descriptor_sets,
+        )
+        .unwrap()
+        .dispatch(group_counts)
+        .unwrap();
+}
+
+/// Records commands into a one-time command buffer, submits it and waits for
+/// it to complete. Used for uploads at startup.
+pub fn submit_and_wait(
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+    record: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+) {
+    let mut builder = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    ).unwrap();
+
+    record(&mut builder);
+
+    builder
+        .build()
+        .unwrap()
+        .execute(queue.clone())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .expect("Could not submit the command buffer.")
+        .wait(None)
+        .unwrap();
 }
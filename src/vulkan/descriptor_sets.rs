@@ -0,0 +1,339 @@
+use std::sync::Arc;
+
+use vulkano::buffer::Subbuffer;
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::image::sampler::Sampler;
+use vulkano::image::view::ImageView;
+use vulkano::pipeline::{ComputePipeline, Pipeline, GraphicsPipeline, PipelineLayout};
+
+use super::shaders;
+
+/// Creates one descriptor set per swapchain image of the ray marcher, laid
+/// out by the pipeline of either backend or the cone march pre-pass. Bindings
+/// the pipeline doesn't use are left out.
+pub fn get_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline_layout: &Arc<PipelineLayout>,
+    scene_buffer: &Subbuffer<shaders::fs::SceneData>,
+    settings_buffers: &[Subbuffer<shaders::fs::Settings>],
+    node_buffer: &Subbuffer<[shaders::fs::Node]>,
+    material_buffer: &Subbuffer<[shaders::fs::Material]>,
+    frame_buffers: &[Subbuffer<shaders::fs::FrameData>],
+    accumulation_image: &Arc<ImageView>,
+    environment_map: &Arc<ImageView>,
+    environment_sampler: &Arc<Sampler>,
+    volume_texture: &Arc<ImageView>,
+    volume_sampler: &Arc<Sampler>,
+    volume_buffer: &Subbuffer<[shaders::fs::Volume]>,
+    cloud_noise: &Arc<ImageView>,
+    cloud_noise_sampler: &Arc<Sampler>,
+    step_histogram_buffer: &Subbuffer<shaders::fs::StepHistogram>,
+    march_stats_buffers: &[Subbuffer<shaders::fs::MarchStats>],
+    cone_starts: &Arc<ImageView>,
+    sampler: &Arc<Sampler>,
+) -> Vec<Arc<PersistentDescriptorSet>> {
+    let layout = &pipeline_layout.set_layouts()[0];
+
+    settings_buffers
+        .iter()
+        .zip(frame_buffers)
+        .zip(march_stats_buffers)
+        .map(|((settings_buffer, frame_buffer), march_stats_buffer)| {
+            let writes = [
+                WriteDescriptorSet::buffer(0, scene_buffer.clone()),
+                WriteDescriptorSet::buffer(1, settings_buffer.clone()),
+                WriteDescriptorSet::buffer(2, node_buffer.clone()),
+                WriteDescriptorSet::buffer(3, material_buffer.clone()),
+                WriteDescriptorSet::buffer(4, frame_buffer.clone()),
+                WriteDescriptorSet::image_view(5, accumulation_image.clone()),
+                WriteDescriptorSet::image_view_sampler(
+                    6,
+                    environment_map.clone(),
+                    environment_sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    7,
+                    volume_texture.clone(),
+                    volume_sampler.clone(),
+                ),
+                WriteDescriptorSet::buffer(8, volume_buffer.clone()),
+                WriteDescriptorSet::image_view_sampler(
+                    9,
+                    cloud_noise.clone(),
+                    cloud_noise_sampler.clone(),
+                ),
+                WriteDescriptorSet::buffer(10, step_histogram_buffer.clone()),
+                WriteDescriptorSet::buffer(11, march_stats_buffer.clone()),
+                WriteDescriptorSet::image_view_sampler(12, cone_starts.clone(), sampler.clone()),
+            ];
+
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                layout.clone(),
+                writes
+                    .into_iter()
+                    .filter(|write| layout.bindings().contains_key(&write.binding())),
+                [],
+            ).expect("Could not create descriptor set.")
+        })
+        .collect()
+}
+
+/// Creates the descriptor sets of the cone march pre-pass levels, coarsest
+/// first, each reading the start distances of the previous one.
+pub fn get_cone_level_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<ComputePipeline>,
+    sampler: &Arc<Sampler>,
+    cone_starts: &[Arc<ImageView>],
+    placeholder: &Arc<ImageView>,
+) -> Vec<Arc<PersistentDescriptorSet>> {
+    cone_starts
+        .iter()
+        .enumerate()
+        .map(|(level, starts)| {
+            let coarse_starts = match level {
+                0 => placeholder,
+                _ => &cone_starts[level - 1],
+            };
+
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                pipeline.layout().set_layouts()[1].clone(),
+                [
+                    WriteDescriptorSet::image_view(0, starts.clone()),
+                    WriteDescriptorSet::image_view_sampler(1, coarse_starts.clone(), sampler.clone()),
+                ],
+                [],
+            ).expect("Could not create descriptor set.")
+        })
+        .collect()
+}
+
+/// Creates the descriptor set of the images the compute ray marcher writes
+/// its outputs to.
+pub fn get_render_target_descriptor_set(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<ComputePipeline>,
+    color: &Arc<ImageView>,
+    normal_depth: &Arc<ImageView>,
+    albedo: &Arc<ImageView>,
+) -> Arc<PersistentDescriptorSet> {
+    PersistentDescriptorSet::new(
+        descriptor_set_allocator,
+        pipeline.layout().set_layouts()[1].clone(),
+        [
+            WriteDescriptorSet::image_view(0, color.clone()),
+            WriteDescriptorSet::image_view(1, normal_depth.clone()),
+            WriteDescriptorSet::image_view(2, albedo.clone()),
+        ],
+        [],
+    ).expect("Could not create descriptor set.")
+}
+
+/// Creates one descriptor set per input image of a post-processing pass. The
+/// input is bound to binding 0 and the guide images to the following ones.
+pub fn get_image_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    sampler: &Arc<Sampler>,
+    inputs: &[Arc<ImageView>],
+    guides: &[Arc<ImageView>],
+) -> Vec<Arc<PersistentDescriptorSet>> {
+    inputs
+        .iter()
+        .map(|input| {
+            let writes = std::iter::once(input)
+                .chain(guides)
+                .enumerate()
+                .map(|(binding, image)| {
+                    WriteDescriptorSet::image_view_sampler(
+                        binding as u32,
+                        image.clone(),
+                        sampler.clone(),
+                    )
+                });
+
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                pipeline.layout().set_layouts()[0].clone(),
+                writes,
+                [],
+            ).expect("Could not create descriptor set.")
+        })
+        .collect()
+}
+
+/// Creates one descriptor set per swapchain image of the TAA resolve, reading
+/// the ray marcher's output, its depth and the history, sampled bilinearly.
+pub fn get_taa_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    sampler: &Arc<Sampler>,
+    color: &Arc<ImageView>,
+    normal_depth: &Arc<ImageView>,
+    history: &Arc<ImageView>,
+    taa_buffers: &[Subbuffer<shaders::taa_fs::TaaData>],
+) -> Vec<Arc<PersistentDescriptorSet>> {
+    taa_buffers
+        .iter()
+        .map(|taa_buffer| {
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, color.clone(), sampler.clone()),
+                    WriteDescriptorSet::image_view_sampler(1, normal_depth.clone(), sampler.clone()),
+                    WriteDescriptorSet::image_view_sampler(2, history.clone(), sampler.clone()),
+                    WriteDescriptorSet::buffer(3, taa_buffer.clone()),
+                ],
+                [],
+            ).expect("Could not create descriptor set.")
+        })
+        .collect()
+}
+
+/// Creates one descriptor set per input image of the luminance histogram.
+pub fn get_histogram_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<ComputePipeline>,
+    sampler: &Arc<Sampler>,
+    inputs: &[Arc<ImageView>],
+    histogram_buffer: &Subbuffer<shaders::histogram_cs::Histogram>,
+) -> Vec<Arc<PersistentDescriptorSet>> {
+    inputs
+        .iter()
+        .map(|input| {
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, input.clone(), sampler.clone()),
+                    WriteDescriptorSet::buffer(1, histogram_buffer.clone()),
+                ],
+                [],
+            ).expect("Could not create descriptor set.")
+        })
+        .collect()
+}
+
+/// Creates one descriptor set per swapchain image of the auto exposure pass.
+pub fn get_exposure_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<ComputePipeline>,
+    histogram_buffer: &Subbuffer<shaders::histogram_cs::Histogram>,
+    exposure_buffer: &Subbuffer<shaders::exposure_cs::Exposure>,
+    tone_map_buffers: &[Subbuffer<shaders::tonemap_fs::ToneMapSettings>],
+) -> Vec<Arc<PersistentDescriptorSet>> {
+    tone_map_buffers
+        .iter()
+        .map(|tone_map_buffer| {
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::buffer(0, histogram_buffer.clone()),
+                    WriteDescriptorSet::buffer(1, exposure_buffer.clone()),
+                    WriteDescriptorSet::buffer(2, tone_map_buffer.clone()),
+                ],
+                [],
+            ).expect("Could not create descriptor set.")
+        })
+        .collect()
+}
+
+/// Creates the descriptor sets of the tone mapping pass, indexed by swapchain
+/// image then by input image.
+pub fn get_tonemap_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    sampler: &Arc<Sampler>,
+    inputs: &[Arc<ImageView>],
+    exposure_buffer: &Subbuffer<shaders::exposure_cs::Exposure>,
+    tone_map_buffers: &[Subbuffer<shaders::tonemap_fs::ToneMapSettings>],
+    lut: &Arc<ImageView>,
+    lut_sampler: &Arc<Sampler>,
+    step_histogram_buffer: &Subbuffer<shaders::fs::StepHistogram>,
+) -> Vec<Vec<Arc<PersistentDescriptorSet>>> {
+    tone_map_buffers
+        .iter()
+        .map(|tone_map_buffer| {
+            inputs
+                .iter()
+                .map(|input| {
+                    PersistentDescriptorSet::new(
+                        descriptor_set_allocator,
+                        pipeline.layout().set_layouts()[0].clone(),
+                        [
+                            WriteDescriptorSet::image_view_sampler(0, input.clone(), sampler.clone()),
+                            WriteDescriptorSet::buffer(1, exposure_buffer.clone()),
+                            WriteDescriptorSet::buffer(2, tone_map_buffer.clone()),
+                            WriteDescriptorSet::image_view_sampler(3, lut.clone(), lut_sampler.clone()),
+                            WriteDescriptorSet::buffer(4, step_histogram_buffer.clone()),
+                        ],
+                        [],
+                    ).expect("Could not create descriptor set.")
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Creates the descriptor sets of a post-processing effect, indexed by
+/// swapchain image then by input image.
+pub fn get_post_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    sampler: &Arc<Sampler>,
+    inputs: &[Arc<ImageView>],
+    bloom_image: &Arc<ImageView>,
+    frame_buffers: &[Subbuffer<shaders::fs::FrameData>],
+) -> Vec<Vec<Arc<PersistentDescriptorSet>>> {
+    frame_buffers
+        .iter()
+        .map(|frame_buffer| {
+            inputs
+                .iter()
+                .map(|input| {
+                    PersistentDescriptorSet::new(
+                        descriptor_set_allocator,
+                        pipeline.layout().set_layouts()[0].clone(),
+                        [
+                            WriteDescriptorSet::image_view_sampler(0, input.clone(), sampler.clone()),
+                            WriteDescriptorSet::image_view_sampler(1, bloom_image.clone(), sampler.clone()),
+                            WriteDescriptorSet::buffer(2, frame_buffer.clone()),
+                        ],
+                        [],
+                    ).expect("Could not create descriptor set.")
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Creates one descriptor set per level of the bloom pyramid on the way up,
+/// reading the level below and the downsampled image of the same level.
+pub fn get_bloom_upsample_descriptor_sets(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    sampler: &Arc<Sampler>,
+    bloom_down: &[Arc<ImageView>],
+    bloom_up: &[Arc<ImageView>],
+) -> Vec<Arc<PersistentDescriptorSet>> {
+    (0..bloom_up.len())
+        .map(|level| {
+            let lower = bloom_up.get(level + 1).unwrap_or(&bloom_down[bloom_down.len() - 1]);
+
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, lower.clone(), sampler.clone()),
+                    WriteDescriptorSet::image_view_sampler(1, bloom_down[level].clone(), sampler.clone()),
+                ],
+                [],
+            ).expect("Could not create descriptor set.")
+        })
+        .collect()
+}
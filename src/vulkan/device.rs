@@ -7,12 +7,14 @@ use vulkano::device::{
     QueueCreateInfo,
     QueueFlags,
     DeviceExtensions,
+    Features,
 };
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
 use vulkano::swapchain::Surface;
 
 pub fn create_device(instance: &Arc<Instance>, surface: &Arc<Surface>) -> (
     DeviceExtensions,
+    Features,
     Arc<PhysicalDevice>,
     Arc<Device>,
     u32,
@@ -23,10 +25,18 @@ pub fn create_device(instance: &Arc<Instance>, surface: &Arc<Surface>) -> (
         ..Default::default()
     };
 
+    // The path tracer accumulates its samples into a storage image from the
+    // fragment shader.
+    let device_features = Features {
+        fragment_stores_and_atomics: true,
+        ..Features::empty()
+    };
+
     let (physical_device, queue_family_index) = choose_physical_device(
         instance,
         surface,
         &device_extensions,
+        &device_features,
     );
 
     let (device, queues) = Device::new(
@@ -37,12 +47,14 @@ pub fn create_device(instance: &Arc<Instance>, surface: &Arc<Surface>) -> (
                 ..Default::default()
             }],
             enabled_extensions: device_extensions,
+            enabled_features: device_features,
             ..Default::default()
         })
         .expect("Could not create Vulkan logical device.");
 
     return (
         device_extensions,
+        device_features,
         physical_device,
         device,
         queue_family_index,
@@ -54,11 +66,13 @@ fn choose_physical_device(
     instance: &Arc<Instance>,
     surface: &Arc<Surface>,
     device_extensions: &DeviceExtensions,
+    device_features: &Features,
 ) -> (Arc<PhysicalDevice>, u32) {
     let physical_device = instance
         .enumerate_physical_devices()
         .expect("Could not enumerate Vulkan physical devices.")
         .filter(|p| p.supported_extensions().contains(device_extensions))
+        .filter(|p| p.supported_features().contains(device_features))
         .filter_map(|p| {
             p.queue_family_properties()
                 .iter()
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use half::f16;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{BufferImageCopy, CopyBufferToImageInfo};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageSubresourceLayers, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+
+use crate::environment::EnvironmentMap;
+
+use super::command_buffers;
+
+/// Uploads the environment map with its full mip chain. Returns the image and
+/// its number of mip levels, `0` when there is no map and a black placeholder
+/// is bound instead.
+pub fn create_environment_image(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+    environment_map: Option<&EnvironmentMap>,
+) -> (Arc<ImageView>, u32) {
+    let placeholder = EnvironmentMap {
+        width: 1,
+        height: 1,
+        pixels: vec![[0.0; 3]],
+    };
+    let base = environment_map.unwrap_or(&placeholder);
+
+    let mut levels = vec![base.clone()];
+    loop {
+        let last = levels.last().unwrap();
+        if last.width == 1 && last.height == 1 {
+            break;
+        }
+        levels.push(last.downsample());
+    }
+
+    let texels = levels
+        .iter()
+        .flat_map(|level| &level.pixels)
+        .flat_map(|&[r, g, b]| [r, g, b, 1.0])
+        .map(|value| f16::from_f32(value).to_bits());
+
+    let staging_buffer = Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        texels,
+    ).expect("Could not create the environment map staging buffer.");
+
+    let image = Image::new(
+        memory_allocator.clone(),
+        ImageCreateInfo {
+            format: Format::R16G16B16A16_SFLOAT,
+            extent: [base.width, base.height, 1],
+            mip_levels: levels.len() as u32,
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    ).expect("Could not create the environment map image.");
+
+    // Each texel is four 16-bit floats.
+    let mut buffer_offset = 0;
+    let regions = levels
+        .iter()
+        .enumerate()
+        .map(|(mip_level, level)| {
+            let region = BufferImageCopy {
+                buffer_offset,
+                image_subresource: ImageSubresourceLayers {
+                    mip_level: mip_level as u32,
+                    ..image.subresource_layers()
+                },
+                image_extent: [level.width, level.height, 1],
+                ..Default::default()
+            };
+            buffer_offset += level.pixels.len() as u64 * 8;
+            region
+        })
+        .collect();
+
+    command_buffers::submit_and_wait(command_buffer_allocator, queue, |builder| {
+        builder
+            .copy_buffer_to_image(CopyBufferToImageInfo {
+                regions,
+                ..CopyBufferToImageInfo::buffer_image(staging_buffer, image.clone())
+            })
+            .unwrap();
+    });
+
+    let level_count = if environment_map.is_some() { levels.len() as u32 } else { 0 };
+
+    (ImageView::new_default(image).unwrap(), level_count)
+}
+
+/// Bilinear sampler wrapping around horizontally, as the map's left and right
+/// edges meet.
+pub fn create_environment_sampler(device: &Arc<Device>) -> Arc<Sampler> {
+    Sampler::new(
+        device.clone(),
+        SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            mipmap_mode: SamplerMipmapMode::Linear,
+            address_mode: [
+                SamplerAddressMode::Repeat,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+            ],
+            ..Default::default()
+        },
+    ).expect("Could not create the environment map sampler.")
+}
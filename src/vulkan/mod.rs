@@ -1,57 +1,338 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use vulkano::buffer::Subbuffer;
 use vulkano::command_buffer::allocator::{
     StandardCommandBufferAllocator,
     StandardCommandBufferAllocatorCreateInfo
 };
-use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyImageInfo, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor_set::allocator::{
+    StandardDescriptorSetAllocator,
+    StandardDescriptorSetAllocatorCreateInfo
+};
 use vulkano::device::physical::PhysicalDevice;
 use vulkano::instance::Instance;
-use vulkano::device::{Device, DeviceExtensions, Queue};
+use vulkano::device::{Device, DeviceExtensions, Features, Queue};
+use vulkano::image::sampler::{Filter, Sampler, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
 use vulkano::memory::allocator::StandardMemoryAllocator;
 use vulkano::pipeline::graphics::viewport::Viewport;
-use vulkano::pipeline::{GraphicsPipeline, PipelineLayout};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineLayout};
+use vulkano::query::QueryPool;
 use vulkano::render_pass::{Framebuffer, RenderPass};
 use vulkano::shader::ShaderModule;
 use vulkano::swapchain::{Surface, Swapchain, SwapchainCreateInfo};
 use winit::event_loop::EventLoop;
 use winit::window::Window;
 
+use crate::atmosphere::Sun;
+use crate::camera::Camera;
+use crate::clouds;
+use crate::environment::EnvironmentMap;
+use crate::lut::Lut;
+use crate::post::PostEffect;
+use crate::profiling::FrameProfiler;
+use crate::resolution::{self, DynamicResolution};
+use crate::scene::Scene;
+use crate::settings::{DebugView, DenoiserSettings, Quality, RenderMode, RenderSettings};
+use crate::volume::VolumeTexture;
+
+use self::render_targets::{RenderTargets, BLOOM_LEVELS, CONE_CELL_SIZES};
 use self::vertex::Vertex;
 
 mod instance;
 mod device;
 mod swapchain;
 mod render_pass;
+mod render_targets;
+mod environment;
+mod volume;
 mod vertex;
 mod shaders;
 mod pipeline;
+mod uniforms;
+mod descriptor_sets;
 mod command_buffers;
+mod timestamps;
+
+/// Horizontal field of view of the camera, in degrees.
+const FIELD_OF_VIEW: f32 = 90.0;
+/// Name of the GPU time of whole frames in `VulkanContext::profiler`.
+pub const GPU_FRAME_TIMING: &str = "GPU frame";
+
+/// Way the ray marcher runs, chosen at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Fragment shader over a fullscreen quad.
+    Graphics,
+    /// Compute shader writing the render targets as storage images.
+    Compute,
+}
+
+/// Ray marching pipeline of the backend in use.
+pub enum Marcher {
+    Graphics(Arc<GraphicsPipeline>),
+    /// With the descriptor set of the render targets it writes, recreated
+    /// along with them.
+    Compute(Arc<ComputePipeline>, Arc<PersistentDescriptorSet>),
+}
+
+/// Iterations of the primary rays, gathered in the iterations debug view or
+/// with `RenderSettings::march_stats`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarchStats {
+    pub rays: u64,
+    pub iterations: u64,
+    /// Iterations the same rays took with plain sphere tracing.
+    pub plain_iterations: u64,
+}
+
+impl MarchStats {
+    pub fn average_iterations(&self) -> f64 {
+        self.iterations as f64 / self.rays.max(1) as f64
+    }
+
+    pub fn average_plain_iterations(&self) -> f64 {
+        self.plain_iterations as f64 / self.rays.max(1) as f64
+    }
+
+    /// Fraction of the plain sphere tracing iterations saved.
+    pub fn savings(&self) -> f64 {
+        1.0 - self.iterations as f64 / self.plain_iterations.max(1) as f64
+    }
+}
+
+/// How the last submitted command buffer of a swapchain image was recorded,
+/// which its results are read back with.
+#[derive(Debug, Clone, Default)]
+pub struct Submission {
+    /// Names of the timed passes.
+    pub passes: Vec<&'static str>,
+    /// Whether it counted the iterations of the primary rays.
+    pub march_stats: bool,
+    /// Whether its GPU pass times are added to `VulkanContext::profiler`.
+    pub profiled: bool,
+}
 
 pub struct VulkanContext {
     pub instance: Arc<Instance>,
     pub surface: Arc<Surface>,
     pub device_extensions: DeviceExtensions,
+    pub device_features: Features,
     pub physical_device: Arc<PhysicalDevice>,
     pub device: Arc<Device>,
     pub queue_family_index: u32,
     pub queue: Arc<Queue>,
     pub swapchain: Arc<Swapchain>,
+    pub scene_render_pass: Arc<RenderPass>,
+    pub post_render_pass: Arc<RenderPass>,
     pub render_pass: Arc<RenderPass>,
     pub framebuffers: Vec<Arc<Framebuffer>>,
     pub memory_allocator: Arc<StandardMemoryAllocator>,
+    pub render_targets: RenderTargets,
+    pub sampler: Arc<Sampler>,
+    /// Bilinear sampler of the bloom pyramid and the upscaling to the window.
+    pub linear_sampler: Arc<Sampler>,
+    pub environment_map: Arc<ImageView>,
+    pub environment_sampler: Arc<Sampler>,
+    pub volume_texture: Arc<ImageView>,
+    pub volume_sampler: Arc<Sampler>,
+    pub cloud_noise: Arc<ImageView>,
+    pub cloud_noise_sampler: Arc<Sampler>,
+    /// Color grading LUT, sampled with `volume_sampler`.
+    pub lut: Arc<ImageView>,
+    /// Input colors of the LUT's first and last entries.
+    pub lut_domain: [[f32; 3]; 2],
     pub vertex_buffer: Subbuffer<[Vertex]>,
+    pub scene_buffer: Subbuffer<shaders::fs::SceneData>,
+    pub settings_buffers: Vec<Subbuffer<shaders::fs::Settings>>,
+    pub node_buffer: Subbuffer<[shaders::fs::Node]>,
+    pub material_buffer: Subbuffer<[shaders::fs::Material]>,
+    pub volume_buffer: Subbuffer<[shaders::fs::Volume]>,
+    pub frame_buffers: Vec<Subbuffer<shaders::fs::FrameData>>,
+    pub tone_map_buffers: Vec<Subbuffer<shaders::tonemap_fs::ToneMapSettings>>,
+    pub taa_buffers: Vec<Subbuffer<shaders::taa_fs::TaaData>>,
+    pub histogram_buffer: Subbuffer<shaders::histogram_cs::Histogram>,
+    pub exposure_buffer: Subbuffer<shaders::exposure_cs::Exposure>,
+    pub step_histogram_buffer: Subbuffer<shaders::fs::StepHistogram>,
+    pub march_stats_buffers: Vec<Subbuffer<shaders::fs::MarchStats>>,
+    /// Iterations of the primary rays read back since the last call to
+    /// `take_march_stats`.
+    pub march_stats: MarchStats,
+    /// Whether the command buffers were recorded to count the iterations of
+    /// the primary rays.
+    pub count_march_stats: bool,
+    /// Encoding of the swapchain images, see `uniforms::output_encoding`.
+    pub output_encoding: u32,
+    /// Number of path traced samples accumulated so far.
+    pub sample_count: u32,
+    pub frame_index: u32,
+    /// Time of the last frame, in seconds.
+    pub time: f32,
+    /// Denoiser settings the command buffers were recorded with, `None` when
+    /// the denoiser is not running.
+    pub denoiser: Option<DenoiserSettings>,
+    /// Post-processing effects the command buffers were recorded with, in
+    /// order.
+    pub post_effects: Vec<PostEffect>,
+    /// Debug view the command buffers were recorded with.
+    pub debug_view: DebugView,
+    /// Whether the command buffers were recorded with the TAA resolve.
+    pub taa: bool,
+    /// Whether the TAA history holds a previous frame.
+    pub taa_history_valid: bool,
+    /// Camera of the previous frame, which the TAA reprojects from.
+    pub previous_camera: Camera,
+    /// Size of the window the swapchain images match.
+    pub window_extent: [u32; 2],
+    /// Fraction of the window's resolution the render targets were created
+    /// at, see `resolution::render_extent`.
+    pub render_scale: f32,
+    pub dynamic_resolution: DynamicResolution,
+    /// Timestamp queries measuring the GPU time of each command buffer,
+    /// `None` if the queue doesn't support them.
+    pub timestamp_pool: Option<Arc<QueryPool>>,
+    /// GPU time of the last completed frame, in milliseconds.
+    pub frame_time: Option<f32>,
+    /// Names of the passes timed by the command buffer of each swapchain
+    /// image.
+    pub timed_passes: Vec<Vec<&'static str>>,
+    /// Last submission of each swapchain image, until its results are read
+    /// back.
+    pub submissions: Vec<Submission>,
+    /// Rolling statistics of the GPU pass times and the frame timings the
+    /// caller adds, such as its CPU timings.
+    pub profiler: FrameProfiler,
+    /// Whether the GPU pass times of the frames submitted from now on are
+    /// added to `profiler`.
+    pub profile_gpu: bool,
+    /// Whether the command buffers were recorded with the cone march
+    /// pre-pass.
+    pub cone_prepass: bool,
+    /// Whether the command buffers were recorded with the histogram and
+    /// exposure passes.
+    pub auto_exposure: bool,
+    /// Viewport of the ray marcher, at the render resolution.
     pub viewport: Viewport,
     pub vs: Arc<ShaderModule>,
-    pub fs: Arc<ShaderModule>,
+    pub atrous_fs: Arc<ShaderModule>,
+    pub tonemap_fs: Arc<ShaderModule>,
+    pub backend: Backend,
+    /// Fragment or compute shader of the ray marcher, depending on the
+    /// backend.
+    pub march_shader: Arc<ShaderModule>,
+    /// Quality preset the ray marching pipeline was specialized with.
+    pub quality: Quality,
+    /// Layout of the ray marching pipeline, shared by the scene descriptor
+    /// sets and push constants.
     pub pipeline_layout: Arc<PipelineLayout>,
-    pub pipeline: Arc<GraphicsPipeline>,
+    pub marcher: Marcher,
+    pub cone_shader: Arc<ShaderModule>,
+    /// Pipelines of the cone march pre-pass levels, coarsest first, sharing
+    /// one layout.
+    pub cone_pipelines: Vec<Arc<ComputePipeline>>,
+    /// Start distances the coarsest cone march level reads, all zero.
+    pub cone_placeholder: Arc<ImageView>,
+    pub atrous_pipeline: Arc<GraphicsPipeline>,
+    pub taa_pipeline: Arc<GraphicsPipeline>,
+    pub tonemap_pipeline: Arc<GraphicsPipeline>,
+    pub bloom_down_pipeline: Arc<GraphicsPipeline>,
+    pub bloom_up_pipeline: Arc<GraphicsPipeline>,
+    pub post_pipeline: Arc<GraphicsPipeline>,
+    pub histogram_pipeline: Arc<ComputePipeline>,
+    pub exposure_pipeline: Arc<ComputePipeline>,
+    pub descriptor_set_allocator: StandardDescriptorSetAllocator,
+    pub descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Scene descriptor sets of the cone march pre-pass, one per swapchain
+    /// image, and those of its levels.
+    pub cone_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    pub cone_level_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Descriptor sets of the denoiser, first bloom level and histogram
+    /// passes, indexed like `post_inputs`.
+    pub denoise_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Descriptor sets of the TAA resolve, one per swapchain image.
+    pub taa_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    pub bloom_input_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Descriptor sets of the bloom levels after the first one on the way
+    /// down, each reading the level above.
+    pub bloom_down_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Descriptor sets of the bloom levels on the way up.
+    pub bloom_up_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    pub histogram_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Descriptor sets of the auto exposure pass, one per swapchain image.
+    pub exposure_descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Descriptor sets of the tone mapping pass, indexed by swapchain image
+    /// then like `post_inputs`.
+    pub tonemap_descriptor_sets: Vec<Vec<Arc<PersistentDescriptorSet>>>,
+    /// Descriptor sets of the post-processing effects, indexed by swapchain
+    /// image then like `post_inputs`.
+    pub post_descriptor_sets: Vec<Vec<Arc<PersistentDescriptorSet>>>,
     pub command_buffer_allocator: StandardCommandBufferAllocator,
     pub command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
 }
 
+/// Denoiser wanted by the settings, `None` if it should not run. Debug views
+/// are not denoised.
+fn active_denoiser(settings: &RenderSettings) -> Option<DenoiserSettings> {
+    (settings.render_mode == RenderMode::PathTraced
+        && settings.denoiser.enabled
+        && settings.debug_view == DebugView::Off)
+        .then(|| settings.denoiser.clone())
+}
+
+/// Whether the settings want the TAA resolve. It only runs on the direct
+/// rendering, the path tracer accumulating its samples already, and not on
+/// debug views.
+fn active_taa(settings: &RenderSettings) -> bool {
+    settings.taa
+        && settings.render_mode == RenderMode::Direct
+        && settings.debug_view == DebugView::Off
+}
+
+/// Whether the settings want the iterations of the primary rays counted,
+/// which the iterations view does on its own.
+fn counts_march_stats(settings: &RenderSettings) -> bool {
+    settings.debug_view == DebugView::Iterations
+        || settings.march_stats && settings.debug_view == DebugView::Off
+}
+
+/// Post-processing effects wanted by the settings, in order. Debug views are
+/// not post-processed.
+fn active_post_effects(settings: &RenderSettings) -> Vec<PostEffect> {
+    if settings.debug_view != DebugView::Off {
+        return Vec::new();
+    }
+
+    settings.post
+        .iter()
+        // Color grading is part of the tone mapping pass.
+        .filter(|pass| pass.enabled && !matches!(pass.effect, PostEffect::ColorGrading { .. }))
+        .map(|pass| pass.effect.clone())
+        .collect()
+}
+
+/// Images a post-processing pass can read from: the ray marcher's output and
+/// the two ping-pong images of the denoiser and post-processing passes.
+fn post_inputs(render_targets: &RenderTargets) -> Vec<Arc<ImageView>> {
+    vec![
+        render_targets.color.clone(),
+        render_targets.post[0].clone(),
+        render_targets.post[1].clone(),
+    ]
+}
+
 impl VulkanContext {
-    pub fn new(event_loop: &EventLoop<()>, window: &Arc<Window>) -> VulkanContext {
+    pub fn new(
+        event_loop: &EventLoop<()>,
+        window: &Arc<Window>,
+        scene: &Scene,
+        environment_map: Option<EnvironmentMap>,
+        volume_texture: Option<VolumeTexture>,
+        lut: Option<Lut>,
+        settings: &RenderSettings,
+        backend: Backend,
+    ) -> VulkanContext {
         let required_extensions = Surface::required_extensions(event_loop);
 
         let instance = instance::create_vulkan_instance(required_extensions);
@@ -61,6 +342,7 @@ impl VulkanContext {
 
         let (
             device_extensions,
+            device_features,
             physical_device,
             device,
             queue_family_index,
@@ -76,7 +358,15 @@ impl VulkanContext {
             window.inner_size().into(),
         );
 
-        let render_pass = render_pass::get_render_pass(&device, &swapchain);
+        let scene_render_pass = render_pass::get_scene_render_pass(&device);
+        let post_render_pass = render_pass::get_post_render_pass(
+            &device,
+            render_pass::SCENE_COLOR_FORMAT,
+        );
+        let render_pass = render_pass::get_post_render_pass(
+            &device,
+            swapchain.image_format(),
+        );
 
         let framebuffers = swapchain::get_framebuffers(
             images.as_slice(),
@@ -87,67 +377,344 @@ impl VulkanContext {
             StandardMemoryAllocator::new_default(device.clone())
         );
 
+        let window_extent: [u32; 2] = window.inner_size().into();
+        let render_scale = if settings.dynamic_resolution { 1.0 } else { settings.render_scale };
+        let render_extent = resolution::render_extent(window_extent, render_scale);
+
+        let render_targets = RenderTargets::new(
+            &memory_allocator,
+            render_extent,
+            &scene_render_pass,
+            &post_render_pass,
+            backend,
+        );
+
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::default())
+            .expect("Could not create sampler.");
+        let linear_sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                ..Default::default()
+            },
+        ).expect("Could not create sampler.");
+
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default()
+        );
+
+        let (environment_map, environment_map_levels) = environment::create_environment_image(
+            &memory_allocator,
+            &command_buffer_allocator,
+            &queue,
+            environment_map.as_ref(),
+        );
+        let environment_sampler = environment::create_environment_sampler(&device);
+        let volume_texture = volume::create_volume_image(
+            &memory_allocator,
+            &command_buffer_allocator,
+            &queue,
+            volume_texture.as_ref(),
+        );
+        let volume_sampler = volume::create_volume_sampler(&device);
+        let cloud_noise = volume::create_cloud_noise_image(
+            &memory_allocator,
+            &command_buffer_allocator,
+            &queue,
+            scene.clouds.as_ref().map(|_| clouds::cloud_noise()).as_deref(),
+        );
+        let cloud_noise_sampler = volume::create_cloud_noise_sampler(&device);
+        let lut_domain = lut
+            .as_ref()
+            .map_or([[0.0; 3], [1.0; 3]], |lut| [lut.domain_min, lut.domain_max]);
+        let lut = volume::create_lut_image(
+            &memory_allocator,
+            &command_buffer_allocator,
+            &queue,
+            lut.as_ref(),
+        );
+        let cone_placeholder = render_targets::create_cone_placeholder(
+            &memory_allocator,
+            &command_buffer_allocator,
+            &queue,
+        );
+
         let vertex_buffer = vertex::create_vertex_buffer(&memory_allocator);
+        let scene_buffer = uniforms::create_scene_buffer(
+            &memory_allocator,
+            scene,
+            environment_map_levels,
+        );
+        let settings_buffers = uniforms::create_settings_buffers(
+            &memory_allocator,
+            settings,
+            framebuffers.len(),
+        );
+        let node_buffer = uniforms::create_node_buffer(&memory_allocator, scene);
+        let material_buffer = uniforms::create_material_buffer(&memory_allocator, scene);
+        let volume_buffer = uniforms::create_volume_buffer(&memory_allocator, scene);
+        let frame_buffers = uniforms::create_frame_buffers(
+            &memory_allocator,
+            &scene.camera,
+            framebuffers.len(),
+        );
+        let output_encoding = uniforms::output_encoding(
+            swapchain.image_format(),
+            swapchain.image_color_space(),
+        );
+        let tone_map_buffers = uniforms::create_tone_map_buffers(
+            &memory_allocator,
+            settings,
+            output_encoding,
+            lut_domain,
+            framebuffers.len(),
+        );
+        let taa_buffers = uniforms::create_taa_buffers(
+            &memory_allocator,
+            &scene.camera,
+            framebuffers.len(),
+        );
+        let histogram_buffer = uniforms::create_histogram_buffer(&memory_allocator);
+        let exposure_buffer = uniforms::create_exposure_buffer(&memory_allocator);
+        let step_histogram_buffer = uniforms::create_step_histogram_buffer(&memory_allocator);
+        let march_stats_buffers = uniforms::create_march_stats_buffers(
+            &memory_allocator,
+            framebuffers.len(),
+        );
+
+        let timestamp_pool = timestamps::create_timestamp_pool(
+            &device,
+            queue_family_index,
+            framebuffers.len(),
+        );
+        let submissions = vec![Submission::default(); framebuffers.len()];
 
         let viewport = Viewport {
             offset: [0.0, 0.0],
-            extent: window.inner_size().into(),
+            extent: render_extent.map(|size| size as f32),
             depth_range: 0.0..=1.0,
         };
 
         let vs = shaders::vs::load(device.clone())
             .expect("Could not load vertex shader.");
-        let fs = shaders::fs::load(device.clone())
-            .expect("Could not load fragment shader.");
+        let atrous_fs = shaders::atrous_fs::load(device.clone())
+            .expect("Could not load denoiser fragment shader.");
+        let taa_fs = shaders::taa_fs::load(device.clone())
+            .expect("Could not load TAA fragment shader.");
+        let tonemap_fs = shaders::tonemap_fs::load(device.clone())
+            .expect("Could not load tone mapping fragment shader.");
+        let bloom_down_fs = shaders::bloom_down_fs::load(device.clone())
+            .expect("Could not load bloom downsampling fragment shader.");
+        let bloom_up_fs = shaders::bloom_up_fs::load(device.clone())
+            .expect("Could not load bloom upsampling fragment shader.");
+        let post_fs = shaders::post_fs::load(device.clone())
+            .expect("Could not load post-processing fragment shader.");
+        let histogram_cs = shaders::histogram_cs::load(device.clone())
+            .expect("Could not load histogram compute shader.");
+        let exposure_cs = shaders::exposure_cs::load(device.clone())
+            .expect("Could not load exposure compute shader.");
+        let cone_shader = shaders::cone_cs::load(device.clone())
+            .expect("Could not load cone march compute shader.");
 
-        let (pipeline_layout, pipeline) = pipeline::get_pipeline::<Vertex>(
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(
+            device.clone(),
+            StandardDescriptorSetAllocatorCreateInfo::default()
+        );
+
+        let march_shader = match backend {
+            Backend::Graphics => shaders::fs::load(device.clone())
+                .expect("Could not load fragment shader."),
+            Backend::Compute => shaders::cs::load(device.clone())
+                .expect("Could not load ray marching compute shader."),
+        };
+        let (pipeline_layout, marcher) = match backend {
+            Backend::Graphics => {
+                let (pipeline_layout, pipeline) = pipeline::get_pipeline::<Vertex>(
+                    &device,
+                    &vs,
+                    &march_shader,
+                    pipeline::march_specialization(settings.quality),
+                    &scene_render_pass,
+                );
+                (pipeline_layout, Marcher::Graphics(pipeline))
+            }
+            Backend::Compute => {
+                let pipeline = pipeline::get_compute_pipeline(
+                    &device,
+                    &march_shader,
+                    pipeline::march_specialization(settings.quality),
+                );
+                let target_descriptor_set = descriptor_sets::get_render_target_descriptor_set(
+                    &descriptor_set_allocator,
+                    &pipeline,
+                    &render_targets.color,
+                    &render_targets.normal_depth,
+                    &render_targets.albedo,
+                );
+                (
+                    pipeline.layout().clone(),
+                    Marcher::Compute(pipeline, target_descriptor_set),
+                )
+            }
+        };
+        let cone_pipelines = pipeline::get_cone_pipelines(&device, &cone_shader, settings.quality);
+        let (_, atrous_pipeline) = pipeline::get_pipeline::<Vertex>(
             &device,
             &vs,
-            &fs,
-            viewport.clone(),
+            &atrous_fs,
+            HashMap::new(),
+            &post_render_pass,
+        );
+        let (_, taa_pipeline) = pipeline::get_pipeline::<Vertex>(
+            &device,
+            &vs,
+            &taa_fs,
+            HashMap::new(),
+            &post_render_pass,
+        );
+        let (_, tonemap_pipeline) = pipeline::get_pipeline::<Vertex>(
+            &device,
+            &vs,
+            &tonemap_fs,
+            HashMap::new(),
             &render_pass,
         );
-
-        let command_buffer_allocator = StandardCommandBufferAllocator::new(
-            device.clone(),
-            StandardCommandBufferAllocatorCreateInfo::default()
+        let (_, bloom_down_pipeline) = pipeline::get_pipeline::<Vertex>(
+            &device,
+            &vs,
+            &bloom_down_fs,
+            HashMap::new(),
+            &post_render_pass,
         );
-
-        let command_buffers = command_buffers::get_command_buffers(
-            &command_buffer_allocator,
-            &queue,
-            &pipeline_layout,
-            &pipeline,
-            &framebuffers,
-            &vertex_buffer,
-            shaders::fs::constants {
-                windowSize: window.inner_size().into(),
-                fov: 90.0,
-                nearPlane: 1.0,
-            }
+        let (_, bloom_up_pipeline) = pipeline::get_pipeline::<Vertex>(
+            &device,
+            &vs,
+            &bloom_up_fs,
+            HashMap::new(),
+            &post_render_pass,
+        );
+        let (_, post_pipeline) = pipeline::get_pipeline::<Vertex>(
+            &device,
+            &vs,
+            &post_fs,
+            HashMap::new(),
+            &post_render_pass,
+        );
+        let histogram_pipeline = pipeline::get_compute_pipeline(
+            &device,
+            &histogram_cs,
+            HashMap::new(),
+        );
+        let exposure_pipeline = pipeline::get_compute_pipeline(
+            &device,
+            &exposure_cs,
+            HashMap::new(),
         );
 
-        VulkanContext {
+        let mut context = VulkanContext {
             instance,
             surface,
             device_extensions,
+            device_features,
             physical_device,
             device,
             queue_family_index,
             queue,
             swapchain,
+            scene_render_pass,
+            post_render_pass,
             render_pass,
             framebuffers,
             memory_allocator,
+            render_targets,
+            sampler,
+            linear_sampler,
+            environment_map,
+            environment_sampler,
+            volume_texture,
+            volume_sampler,
+            cloud_noise,
+            cloud_noise_sampler,
+            lut,
+            lut_domain,
             vertex_buffer,
+            scene_buffer,
+            settings_buffers,
+            node_buffer,
+            material_buffer,
+            volume_buffer,
+            frame_buffers,
+            tone_map_buffers,
+            taa_buffers,
+            histogram_buffer,
+            exposure_buffer,
+            step_histogram_buffer,
+            march_stats_buffers,
+            march_stats: MarchStats::default(),
+            count_march_stats: counts_march_stats(settings),
+            output_encoding,
+            sample_count: 0,
+            frame_index: 0,
+            time: 0.0,
+            denoiser: active_denoiser(settings),
+            post_effects: active_post_effects(settings),
+            debug_view: settings.debug_view,
+            taa: active_taa(settings),
+            taa_history_valid: false,
+            previous_camera: scene.camera,
+            window_extent,
+            render_scale,
+            dynamic_resolution: DynamicResolution::default(),
+            timestamp_pool,
+            frame_time: None,
+            timed_passes: Vec::new(),
+            submissions,
+            profiler: FrameProfiler::default(),
+            profile_gpu: true,
+            cone_prepass: settings.cone_prepass,
+            auto_exposure: settings.auto_exposure,
             viewport,
             vs,
-            fs,
+            atrous_fs,
+            tonemap_fs,
+            backend,
+            march_shader,
+            quality: settings.quality,
             pipeline_layout,
-            pipeline,
+            marcher,
+            cone_shader,
+            cone_pipelines,
+            cone_placeholder,
+            atrous_pipeline,
+            taa_pipeline,
+            tonemap_pipeline,
+            bloom_down_pipeline,
+            bloom_up_pipeline,
+            post_pipeline,
+            histogram_pipeline,
+            exposure_pipeline,
+            descriptor_set_allocator,
+            descriptor_sets: Vec::new(),
+            cone_descriptor_sets: Vec::new(),
+            cone_level_descriptor_sets: Vec::new(),
+            denoise_descriptor_sets: Vec::new(),
+            taa_descriptor_sets: Vec::new(),
+            bloom_input_descriptor_sets: Vec::new(),
+            bloom_down_descriptor_sets: Vec::new(),
+            bloom_up_descriptor_sets: Vec::new(),
+            histogram_descriptor_sets: Vec::new(),
+            exposure_descriptor_sets: Vec::new(),
+            tonemap_descriptor_sets: Vec::new(),
+            post_descriptor_sets: Vec::new(),
             command_buffer_allocator,
-            command_buffers,
-        }
+            command_buffers: Vec::new(),
+        };
+        context.create_descriptor_sets();
+        context.record_command_buffers();
+
+        context
     }
 
     pub fn recreate_swapchain(
@@ -165,34 +732,637 @@ impl VulkanContext {
                 }
             ).expect("Failed to recreate swapchain: {e}");
         self.swapchain = new_swapchain;
-        let new_framebuffers = swapchain::get_framebuffers(
+        self.framebuffers = swapchain::get_framebuffers(
             &new_images,
             &self.render_pass,
         );
 
         if window_resized {
-            self.viewport.extent = new_dimensions.into();
-            let new_pipeline = pipeline::get_pipeline::<Vertex>(
-                &self.device,
-                &self.vs,
-                &self.fs,
-                self.viewport.clone(),
-                &self.render_pass,
+            self.window_extent = new_dimensions.into();
+            self.recreate_render_targets();
+        }
+
+        self.record_command_buffers();
+    }
+
+    /// Recreates the render targets at the current render scale of the
+    /// window's size, along with the descriptor sets using them. The command
+    /// buffers must be recorded again.
+    fn recreate_render_targets(&mut self) {
+        let render_extent = resolution::render_extent(self.window_extent, self.render_scale);
+        self.viewport.extent = render_extent.map(|size| size as f32);
+        self.render_targets = RenderTargets::new(
+            &self.memory_allocator,
+            render_extent,
+            &self.scene_render_pass,
+            &self.post_render_pass,
+            self.backend,
+        );
+        self.sample_count = 0;
+        self.taa_history_valid = false;
+
+        if let Marcher::Compute(pipeline, target_descriptor_set) = &mut self.marcher {
+            *target_descriptor_set = descriptor_sets::get_render_target_descriptor_set(
+                &self.descriptor_set_allocator,
+                pipeline,
+                &self.render_targets.color,
+                &self.render_targets.normal_depth,
+                &self.render_targets.albedo,
             );
-            (self.pipeline_layout, self.pipeline) = new_pipeline;
-            self.command_buffers = command_buffers::get_command_buffers(
-                &self.command_buffer_allocator,
-                &self.queue,
-                &self.pipeline_layout,
-                &self.pipeline,
-                &new_framebuffers,
-                &self.vertex_buffer,
-                shaders::fs::constants {
-                    windowSize: window.inner_size().into(),
-                    fov: 90.0,
+        }
+
+        self.create_descriptor_sets();
+    }
+
+    /// Creates the descriptor sets of every pass but the compute ray
+    /// marcher's render targets, from the current render targets and buffers.
+    fn create_descriptor_sets(&mut self) {
+        self.descriptor_sets = descriptor_sets::get_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.pipeline_layout,
+            &self.scene_buffer,
+            &self.settings_buffers,
+            &self.node_buffer,
+            &self.material_buffer,
+            &self.frame_buffers,
+            &self.render_targets.accumulation,
+            &self.environment_map,
+            &self.environment_sampler,
+            &self.volume_texture,
+            &self.volume_sampler,
+            &self.volume_buffer,
+            &self.cloud_noise,
+            &self.cloud_noise_sampler,
+            &self.step_histogram_buffer,
+            &self.march_stats_buffers,
+            &self.render_targets.cone_starts[CONE_CELL_SIZES.len() - 1],
+            &self.sampler,
+        );
+        self.cone_descriptor_sets = descriptor_sets::get_descriptor_sets(
+            &self.descriptor_set_allocator,
+            self.cone_pipelines[0].layout(),
+            &self.scene_buffer,
+            &self.settings_buffers,
+            &self.node_buffer,
+            &self.material_buffer,
+            &self.frame_buffers,
+            &self.render_targets.accumulation,
+            &self.environment_map,
+            &self.environment_sampler,
+            &self.volume_texture,
+            &self.volume_sampler,
+            &self.volume_buffer,
+            &self.cloud_noise,
+            &self.cloud_noise_sampler,
+            &self.step_histogram_buffer,
+            &self.march_stats_buffers,
+            &self.render_targets.cone_starts[CONE_CELL_SIZES.len() - 1],
+            &self.sampler,
+        );
+        self.cone_level_descriptor_sets = descriptor_sets::get_cone_level_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.cone_pipelines[0],
+            &self.sampler,
+            &self.render_targets.cone_starts,
+            &self.cone_placeholder,
+        );
+        self.denoise_descriptor_sets = descriptor_sets::get_image_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.atrous_pipeline,
+            &self.sampler,
+            &post_inputs(&self.render_targets),
+            &[
+                self.render_targets.normal_depth.clone(),
+                self.render_targets.albedo.clone(),
+            ],
+        );
+        self.taa_descriptor_sets = descriptor_sets::get_taa_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.taa_pipeline,
+            &self.linear_sampler,
+            &self.render_targets.color,
+            &self.render_targets.normal_depth,
+            &self.render_targets.history,
+            &self.taa_buffers,
+        );
+        self.bloom_input_descriptor_sets = descriptor_sets::get_image_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.bloom_down_pipeline,
+            &self.linear_sampler,
+            &post_inputs(&self.render_targets),
+            &[],
+        );
+        self.bloom_down_descriptor_sets = descriptor_sets::get_image_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.bloom_down_pipeline,
+            &self.linear_sampler,
+            &self.render_targets.bloom_down[..BLOOM_LEVELS - 1],
+            &[],
+        );
+        self.bloom_up_descriptor_sets = descriptor_sets::get_bloom_upsample_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.bloom_up_pipeline,
+            &self.linear_sampler,
+            &self.render_targets.bloom_down,
+            &self.render_targets.bloom_up,
+        );
+        self.post_descriptor_sets = descriptor_sets::get_post_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.post_pipeline,
+            &self.linear_sampler,
+            &post_inputs(&self.render_targets),
+            &self.render_targets.bloom_up[0],
+            &self.frame_buffers,
+        );
+        self.histogram_descriptor_sets = descriptor_sets::get_histogram_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.histogram_pipeline,
+            &self.sampler,
+            &post_inputs(&self.render_targets),
+            &self.histogram_buffer,
+        );
+        self.exposure_descriptor_sets = descriptor_sets::get_exposure_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.exposure_pipeline,
+            &self.histogram_buffer,
+            &self.exposure_buffer,
+            &self.tone_map_buffers,
+        );
+        self.tonemap_descriptor_sets = descriptor_sets::get_tonemap_descriptor_sets(
+            &self.descriptor_set_allocator,
+            &self.tonemap_pipeline,
+            &self.linear_sampler,
+            &post_inputs(&self.render_targets),
+            &self.exposure_buffer,
+            &self.tone_map_buffers,
+            &self.lut,
+            &self.volume_sampler,
+            &self.step_histogram_buffer,
+        );
+    }
+
+    /// Records one command buffer per swapchain image: the cone march
+    /// pre-pass if enabled, the ray marcher, the denoiser passes or the TAA
+    /// resolve if enabled, the post-processing effects, the auto exposure if
+    /// enabled, then the tone mapping and upscaling into the swapchain image.
+    /// The GPU time of each pass is measured when timestamps are supported.
+    fn record_command_buffers(&mut self) {
+        (self.command_buffers, self.timed_passes) = self.framebuffers
+            .iter()
+            .zip(&self.descriptor_sets)
+            .enumerate()
+            .map(|(image_i, (framebuffer, descriptor_set))| {
+                let mut builder = AutoCommandBufferBuilder::primary(
+                    &self.command_buffer_allocator,
+                    self.queue.queue_family_index(),
+                    CommandBufferUsage::MultipleSubmit,
+                ).unwrap();
+
+                let mut timer = timestamps::PassTimer::start(
+                    &mut builder,
+                    self.timestamp_pool.as_ref(),
+                    image_i,
+                );
+
+                if self.debug_view == DebugView::StepHistogram {
+                    builder
+                        .fill_buffer(self.step_histogram_buffer.clone().reinterpret(), 0)
+                        .unwrap();
+                }
+                if self.count_march_stats {
+                    builder
+                        .fill_buffer(self.march_stats_buffers[image_i].clone().reinterpret(), 0)
+                        .unwrap();
+                }
+
+                let constants = shaders::fs::constants {
+                    windowSize: self.viewport.extent,
+                    fov: FIELD_OF_VIEW,
                     nearPlane: 1.0,
+                    debugView: uniforms::debug_view(self.debug_view),
+                };
+                let [width, height] = self.viewport.extent.map(|extent| extent as u32);
+
+                if self.cone_prepass {
+                    let levels = self.cone_pipelines
+                        .iter()
+                        .zip(&self.cone_level_descriptor_sets)
+                        .zip(&self.render_targets.cone_starts);
+                    for ((pipeline, level_descriptor_set), starts) in levels {
+                        let [level_width, level_height, _] = starts.image().extent();
+
+                        builder
+                            .push_constants(pipeline.layout().clone(), 0, constants)
+                            .unwrap();
+                        command_buffers::dispatch(
+                            &mut builder,
+                            pipeline,
+                            (
+                                self.cone_descriptor_sets[image_i].clone(),
+                                level_descriptor_set.clone(),
+                            ),
+                            [level_width.div_ceil(8), level_height.div_ceil(8), 1],
+                        );
+                    }
+                    timer.end_pass(&mut builder, "Cone march pre-pass");
+                }
+
+                match &self.marcher {
+                    Marcher::Graphics(pipeline) => {
+                        command_buffers::begin_fullscreen_pass(
+                            &mut builder,
+                            &self.render_targets.scene_framebuffer,
+                            pipeline,
+                            &self.vertex_buffer,
+                            descriptor_set,
+                        );
+                        builder
+                            .push_constants(self.pipeline_layout.clone(), 0, constants)
+                            .unwrap();
+                        command_buffers::end_fullscreen_pass(&mut builder, &self.vertex_buffer);
+                    }
+                    Marcher::Compute(pipeline, target_descriptor_set) => {
+                        // The push constant block is shared with the fragment
+                        // shader.
+                        builder
+                            .push_constants(self.pipeline_layout.clone(), 0, constants)
+                            .unwrap();
+                        command_buffers::dispatch(
+                            &mut builder,
+                            pipeline,
+                            (descriptor_set.clone(), target_descriptor_set.clone()),
+                            [width.div_ceil(8), height.div_ceil(8), 1],
+                        );
+                    }
+                }
+                timer.end_pass(&mut builder, "Ray marching");
+
+                // Index in `post_inputs` of the image holding the latest result.
+                let mut output = 0;
+
+                if let Some(denoiser) = &self.denoiser {
+                    for iteration in 0..denoiser.iterations {
+                        let target = iteration as usize % 2;
+
+                        command_buffers::begin_fullscreen_pass(
+                            &mut builder,
+                            &self.render_targets.post_framebuffers[target],
+                            &self.atrous_pipeline,
+                            &self.vertex_buffer,
+                            &self.denoise_descriptor_sets[output],
+                        );
+                        builder
+                            .push_constants(
+                                self.atrous_pipeline.layout().clone(),
+                                0,
+                                shaders::atrous_fs::constants {
+                                    stepWidth: 1 << iteration,
+                                    // The color weight tightens as the
+                                    // image gets smoother.
+                                    colorPhi: denoiser.color_phi / (1 << iteration) as f32,
+                                    normalPhi: denoiser.normal_phi,
+                                    depthPhi: denoiser.depth_phi,
+                                    albedoPhi: denoiser.albedo_phi,
+                                },
+                            )
+                            .unwrap();
+                        command_buffers::end_fullscreen_pass(&mut builder, &self.vertex_buffer);
+
+                        output = target + 1;
+                    }
+                    timer.end_pass(&mut builder, "Denoiser");
+                }
+
+                // The TAA never runs along with the denoiser, so it always
+                // reads the ray marcher's output.
+                if self.taa {
+                    command_buffers::begin_fullscreen_pass(
+                        &mut builder,
+                        &self.render_targets.post_framebuffers[0],
+                        &self.taa_pipeline,
+                        &self.vertex_buffer,
+                        &self.taa_descriptor_sets[image_i],
+                    );
+                    builder
+                        .push_constants(
+                            self.taa_pipeline.layout().clone(),
+                            0,
+                            shaders::taa_fs::constants { fov: FIELD_OF_VIEW },
+                        )
+                        .unwrap();
+                    command_buffers::end_fullscreen_pass(&mut builder, &self.vertex_buffer);
+
+                    builder
+                        .copy_image(CopyImageInfo::images(
+                            self.render_targets.post[0].image().clone(),
+                            self.render_targets.history.image().clone(),
+                        ))
+                        .unwrap();
+
+                    output = 1;
+                    timer.end_pass(&mut builder, "TAA");
+                }
+
+                for effect in &self.post_effects {
+                    if let PostEffect::Bloom { threshold, radius, .. } = *effect {
+                        self.record_bloom(&mut builder, output, threshold, radius);
+                    }
+
+                    let target = if output == 1 { 1 } else { 0 };
+
+                    command_buffers::begin_fullscreen_pass(
+                        &mut builder,
+                        &self.render_targets.post_framebuffers[target],
+                        &self.post_pipeline,
+                        &self.vertex_buffer,
+                        &self.post_descriptor_sets[image_i][output],
+                    );
+                    builder
+                        .push_constants(
+                            self.post_pipeline.layout().clone(),
+                            0,
+                            uniforms::post_constants(effect),
+                        )
+                        .unwrap();
+                    command_buffers::end_fullscreen_pass(&mut builder, &self.vertex_buffer);
+
+                    output = target + 1;
+                }
+                if !self.post_effects.is_empty() {
+                    timer.end_pass(&mut builder, "Post-processing");
+                }
+
+                if self.auto_exposure {
+                    command_buffers::dispatch(
+                        &mut builder,
+                        &self.histogram_pipeline,
+                        self.histogram_descriptor_sets[output].clone(),
+                        [width.div_ceil(16), height.div_ceil(16), 1],
+                    );
+                    command_buffers::dispatch(
+                        &mut builder,
+                        &self.exposure_pipeline,
+                        self.exposure_descriptor_sets[image_i].clone(),
+                        [1, 1, 1],
+                    );
+                    timer.end_pass(&mut builder, "Auto exposure");
                 }
+
+                command_buffers::begin_fullscreen_pass(
+                    &mut builder,
+                    framebuffer,
+                    &self.tonemap_pipeline,
+                    &self.vertex_buffer,
+                    &self.tonemap_descriptor_sets[image_i][output],
+                );
+                builder
+                    .push_constants(
+                        self.tonemap_pipeline.layout().clone(),
+                        0,
+                        shaders::tonemap_fs::constants {
+                            outputSize: framebuffer.extent().map(|size| size as f32),
+                            debugView: uniforms::debug_view(self.debug_view),
+                        },
+                    )
+                    .unwrap();
+                command_buffers::end_fullscreen_pass(&mut builder, &self.vertex_buffer);
+                timer.end_pass(&mut builder, "Tone mapping");
+
+                (builder.build().unwrap(), timer.finish())
+            })
+            .unzip();
+    }
+
+    /// Recreates the ray marching and cone march pipelines with the
+    /// specialization constants of the current quality preset. Their
+    /// descriptor set layouts don't change, so the descriptor sets are kept.
+    fn recreate_marcher_pipeline(&mut self) {
+        let specialization = pipeline::march_specialization(self.quality);
+        self.cone_pipelines = pipeline::get_cone_pipelines(
+            &self.device,
+            &self.cone_shader,
+            self.quality,
+        );
+
+        match &mut self.marcher {
+            Marcher::Graphics(pipeline) => {
+                let (pipeline_layout, new_pipeline) = pipeline::get_pipeline::<Vertex>(
+                    &self.device,
+                    &self.vs,
+                    &self.march_shader,
+                    specialization,
+                    &self.scene_render_pass,
+                );
+                self.pipeline_layout = pipeline_layout;
+                *pipeline = new_pipeline;
+            }
+            Marcher::Compute(pipeline, _) => {
+                *pipeline = pipeline::get_compute_pipeline(
+                    &self.device,
+                    &self.march_shader,
+                    specialization,
+                );
+                self.pipeline_layout = pipeline.layout().clone();
+            }
+        }
+    }
+
+    /// Records the bloom pyramid of the image at index `input` in
+    /// `post_inputs`, leaving the blurred glow in the top level on the way up.
+    fn record_bloom(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        input: usize,
+        threshold: f32,
+        radius: f32,
+    ) {
+        for level in 0..BLOOM_LEVELS {
+            let descriptor_set = match level {
+                0 => &self.bloom_input_descriptor_sets[input],
+                _ => &self.bloom_down_descriptor_sets[level - 1],
+            };
+
+            command_buffers::begin_fullscreen_pass(
+                builder,
+                &self.render_targets.bloom_down_framebuffers[level],
+                &self.bloom_down_pipeline,
+                &self.vertex_buffer,
+                descriptor_set,
             );
+            builder
+                .push_constants(
+                    self.bloom_down_pipeline.layout().clone(),
+                    0,
+                    shaders::bloom_down_fs::constants {
+                        firstLevel: (level == 0) as u32,
+                        threshold,
+                    },
+                )
+                .unwrap();
+            command_buffers::end_fullscreen_pass(builder, &self.vertex_buffer);
+        }
+
+        for level in (0..BLOOM_LEVELS - 1).rev() {
+            command_buffers::begin_fullscreen_pass(
+                builder,
+                &self.render_targets.bloom_up_framebuffers[level],
+                &self.bloom_up_pipeline,
+                &self.vertex_buffer,
+                &self.bloom_up_descriptor_sets[level],
+            );
+            builder
+                .push_constants(
+                    self.bloom_up_pipeline.layout().clone(),
+                    0,
+                    shaders::bloom_up_fs::constants { radius },
+                )
+                .unwrap();
+            command_buffers::end_fullscreen_pass(builder, &self.vertex_buffer);
+        }
+    }
+
+    /// Uploads the settings, camera, sun and time in seconds used by the
+    /// command buffer of the given swapchain image, and gathers the march
+    /// statistics and GPU pass times of its previous submission, which must
+    /// have completed, see `read_submission`. The render scale follows the
+    /// GPU time when the dynamic resolution is enabled.
+    pub fn update_frame(
+        &mut self,
+        image_i: usize,
+        settings: &RenderSettings,
+        camera: &Camera,
+        sun: &Sun,
+        time: f32,
+    ) {
+        self.frame_time = self.read_submission(image_i);
+        if let Some(frame_time) = self.frame_time {
+            self.dynamic_resolution.add_frame_time(frame_time);
+        }
+
+        let render_scale = if settings.dynamic_resolution {
+            self.dynamic_resolution.adjust(self.render_scale, settings.target_frame_time, time)
+        } else {
+            settings.render_scale
+        };
+        if render_scale != self.render_scale {
+            self.render_scale = render_scale;
+            self.dynamic_resolution.reset();
+            self.recreate_render_targets();
+            self.record_command_buffers();
         }
+
+        if settings.quality != self.quality {
+            self.quality = settings.quality;
+            self.recreate_marcher_pipeline();
+            self.record_command_buffers();
+        }
+
+        let denoiser = active_denoiser(settings);
+        let taa = active_taa(settings);
+        let post_effects = active_post_effects(settings);
+        if denoiser != self.denoiser
+            || taa != self.taa
+            || post_effects != self.post_effects
+            || settings.debug_view != self.debug_view
+            || settings.cone_prepass != self.cone_prepass
+            || settings.auto_exposure != self.auto_exposure
+            || counts_march_stats(settings) != self.count_march_stats
+        {
+            self.denoiser = denoiser;
+            if taa != self.taa {
+                self.taa = taa;
+                self.taa_history_valid = false;
+            }
+            self.post_effects = post_effects;
+            self.debug_view = settings.debug_view;
+            self.cone_prepass = settings.cone_prepass;
+            self.auto_exposure = settings.auto_exposure;
+            self.count_march_stats = counts_march_stats(settings);
+            self.record_command_buffers();
+        }
+
+        *self.settings_buffers[image_i].write().unwrap() = uniforms::settings_data(settings);
+        *self.frame_buffers[image_i].write().unwrap() = uniforms::frame_data(
+            camera,
+            sun,
+            time,
+            self.sample_count,
+            self.frame_index,
+            if self.taa { uniforms::taa_jitter(self.frame_index) } else { [0.0; 2] },
+        );
+        *self.taa_buffers[image_i].write().unwrap() = uniforms::taa_data(
+            camera,
+            &self.previous_camera,
+            self.taa_history_valid,
+        );
+        self.previous_camera = *camera;
+        self.taa_history_valid = self.taa;
+        *self.tone_map_buffers[image_i].write().unwrap() = uniforms::tone_map_data(
+            settings,
+            self.output_encoding,
+            self.lut_domain,
+            time - self.time,
+        );
+        self.time = time;
+
+        // The command buffer about to be submitted, possibly just recorded.
+        self.submissions[image_i] = Submission {
+            passes: self.timed_passes[image_i].clone(),
+            march_stats: self.count_march_stats,
+            profiled: self.profile_gpu,
+        };
+
+        self.frame_index = self.frame_index.wrapping_add(1);
+        if settings.render_mode == RenderMode::PathTraced {
+            self.sample_count += 1;
+        }
+    }
+
+    /// Gathers the march statistics and GPU pass times of the image's last
+    /// submission, which must have completed, and forgets it so it is only
+    /// read once. The pass times are added to `profiler` when the submission
+    /// was profiled. Returns the GPU time of the whole frame, `None` if it
+    /// isn't available.
+    fn read_submission(&mut self, image_i: usize) -> Option<f32> {
+        let submission = std::mem::take(&mut self.submissions[image_i]);
+        if submission.march_stats {
+            let stats = self.march_stats_buffers[image_i].read().unwrap();
+            self.march_stats.rays += stats.rayCount as u64;
+            self.march_stats.iterations += stats.iterations as u64;
+            self.march_stats.plain_iterations += stats.plainIterations as u64;
+        }
+
+        let timestamp_pool = self.timestamp_pool.as_ref()?;
+        let pass_times =
+            timestamps::read_pass_times(timestamp_pool, image_i, &submission.passes)?;
+        let frame_time = pass_times.iter().map(|(_, time)| time).sum();
+        if submission.profiled {
+            for (pass, time) in pass_times {
+                self.profiler.add(pass, time);
+            }
+            self.profiler.add(GPU_FRAME_TIMING, frame_time);
+        }
+        Some(frame_time)
+    }
+
+    /// Gathers the march statistics and GPU pass times of the submissions
+    /// `update_frame` hasn't read back yet. Every submission must have
+    /// completed, such as after waiting for the device to be idle.
+    pub fn read_remaining_submissions(&mut self) {
+        for image_i in 0..self.submissions.len() {
+            self.read_submission(image_i);
+        }
+    }
+
+    /// Returns the march statistics gathered since the last call and starts
+    /// over.
+    pub fn take_march_stats(&mut self) -> MarchStats {
+        std::mem::take(&mut self.march_stats)
+    }
+
+    /// Discards the path traced samples, to be called whenever the rendered
+    /// image changes.
+    pub fn reset_accumulation(&mut self) {
+        self.sample_count = 0;
     }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use vulkano::device::Device;
@@ -8,16 +9,53 @@ use vulkano::pipeline::graphics::rasterization::RasterizationState;
 use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexDefinition};
 use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
 use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
-use vulkano::pipeline::{GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
-use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::compute::ComputePipelineCreateInfo;
+use vulkano::pipeline::{ComputePipeline, DynamicState, GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
+use vulkano::pipeline::graphics::viewport::ViewportState;
 use vulkano::render_pass::{RenderPass, Subpass};
-use vulkano::shader::ShaderModule;
+use vulkano::shader::{ShaderModule, SpecializationConstant};
 
+use crate::settings::Quality;
+
+use super::render_targets::CONE_CELL_SIZES;
+
+/// Specialization constants of the ray marcher's march limits, see
+/// ray-marcher.glsl.
+pub fn march_specialization(quality: Quality) -> HashMap<u32, SpecializationConstant> {
+    [
+        (0, quality.max_iterations().into()),
+        (1, quality.max_distance().into()),
+        (2, quality.hit_distance().into()),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Creates the pipelines of the cone march pre-pass levels, coarsest first,
+/// specialized with their cell size.
+pub fn get_cone_pipelines(
+    device: &Arc<Device>,
+    cs: &Arc<ShaderModule>,
+    quality: Quality,
+) -> Vec<Arc<ComputePipeline>> {
+    CONE_CELL_SIZES
+        .iter()
+        .map(|&cell_size| {
+            let mut specialization = march_specialization(quality);
+            specialization.insert(3, (cell_size as i32).into());
+            get_compute_pipeline(device, cs, specialization)
+        })
+        .collect()
+}
+
+/// Creates a graphics pipeline drawing into the first subpass of the render
+/// pass, with the fragment shader specialized by the given constants. The
+/// viewport is dynamic, set when a pass begins.
 pub fn get_pipeline<V>(
     device: &Arc<Device>,
     vs: &Arc<ShaderModule>,
     fs: &Arc<ShaderModule>,
-    viewport: Viewport,
+    specialization: HashMap<u32, SpecializationConstant>,
     render_pass: &Arc<RenderPass>,
 ) -> (Arc<PipelineLayout>, Arc<GraphicsPipeline>)
 where
@@ -25,7 +63,11 @@ where
 {
 
     let vs = vs.entry_point("main").unwrap();
-    let fs = fs.entry_point("main").unwrap();
+    let fs = fs
+        .specialize(specialization)
+        .expect("Could not specialize fragment shader.")
+        .entry_point("main")
+        .unwrap();
 
     let vertex_input_state = V::per_vertex()
         .definition(&vs.info().input_interface)
@@ -54,19 +96,43 @@ where
                 stages: stages.into_iter().collect(),
                 vertex_input_state: Some(vertex_input_state),
                 input_assembly_state: Some(InputAssemblyState::default()),
-                viewport_state: Some(ViewportState {
-                    viewports: [viewport].into_iter().collect(),
-                    ..Default::default()
-                }),
+                viewport_state: Some(ViewportState::default()),
                 rasterization_state: Some(RasterizationState::default()),
                 multisample_state: Some(MultisampleState::default()),
                 color_blend_state: Some(ColorBlendState::with_attachment_states(
                     subpass.num_color_attachments(),
                     ColorBlendAttachmentState::default(),
                 )),
+                dynamic_state: [DynamicState::Viewport].into_iter().collect(),
                 subpass: Some(subpass.into()),
                 ..GraphicsPipelineCreateInfo::layout(layout.clone())
             },
         ).unwrap(),
     )
 }
+
+pub fn get_compute_pipeline(
+    device: &Arc<Device>,
+    cs: &Arc<ShaderModule>,
+    specialization: HashMap<u32, SpecializationConstant>,
+) -> Arc<ComputePipeline> {
+    let cs = cs
+        .specialize(specialization)
+        .expect("Could not specialize compute shader.")
+        .entry_point("main")
+        .unwrap();
+    let stage = PipelineShaderStageCreateInfo::new(cs);
+
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+            .into_pipeline_layout_create_info(device.clone())
+            .unwrap(),
+    ).unwrap();
+
+    ComputePipeline::new(
+        device.clone(),
+        None,
+        ComputePipelineCreateInfo::stage_layout(stage, layout),
+    ).unwrap()
+}
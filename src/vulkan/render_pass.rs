@@ -1,24 +1,39 @@
 use std::sync::Arc;
+use vulkano::format::Format;
 use vulkano::render_pass::RenderPass;
 use vulkano::device::Device;
-use vulkano::swapchain::Swapchain;
 
-pub fn get_render_pass(
-    device: &Arc<Device>,
-    swapchain: &Arc<Swapchain>
-) -> Arc<RenderPass> {
+/// Format of the color and normal/depth targets written by the ray marcher.
+pub const SCENE_COLOR_FORMAT: Format = Format::R32G32B32A32_SFLOAT;
+pub const ALBEDO_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+/// Render pass of the ray marcher, which outputs the shaded color along with
+/// the first hit's normal, depth and albedo.
+pub fn get_scene_render_pass(device: &Arc<Device>) -> Arc<RenderPass> {
     let render_pass = vulkano::single_pass_renderpass!(
         device.clone(),
         attachments: {
             color: {
-                format: swapchain.image_format(),
+                format: SCENE_COLOR_FORMAT,
+                samples: 1,
+                load_op: Clear,
+                store_op: Store,
+            },
+            normal_depth: {
+                format: SCENE_COLOR_FORMAT,
+                samples: 1,
+                load_op: Clear,
+                store_op: Store,
+            },
+            albedo: {
+                format: ALBEDO_FORMAT,
                 samples: 1,
                 load_op: Clear,
                 store_op: Store,
             },
         },
         pass: {
-            color: [color],
+            color: [color, normal_depth, albedo],
             depth_stencil: {},
         },
     ).unwrap();
@@ -26,4 +41,27 @@ pub fn get_render_pass(
     render_pass
 }
 
+/// Render pass of a fullscreen post-processing pass, every pixel of its
+/// single attachment is overwritten.
+pub fn get_post_render_pass(
+    device: &Arc<Device>,
+    format: Format,
+) -> Arc<RenderPass> {
+    let render_pass = vulkano::single_pass_renderpass!(
+        device.clone(),
+        attachments: {
+            color: {
+                format: format,
+                samples: 1,
+                load_op: DontCare,
+                store_op: Store,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {},
+        },
+    ).unwrap();
 
+    render_pass
+}
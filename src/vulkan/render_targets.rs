@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::ClearColorImageInfo;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, StandardMemoryAllocator};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
+
+use super::command_buffers;
+use super::render_pass::{ALBEDO_FORMAT, SCENE_COLOR_FORMAT};
+use super::Backend;
+
+/// Number of levels of the bloom pyramid, each half the size of the previous
+/// one.
+pub const BLOOM_LEVELS: usize = 6;
+
+/// Pixels per texel of the cone march pre-pass levels, coarsest first. Must
+/// match ray-marcher.glsl.
+pub const CONE_CELL_SIZES: [u32; 2] = [8, 4];
+
+/// Offscreen images sized like the window, recreated when it is resized.
+pub struct RenderTargets {
+    pub color: Arc<ImageView>,
+    /// First hit normal in `xyz` and its distance from the camera in `w`.
+    pub normal_depth: Arc<ImageView>,
+    pub albedo: Arc<ImageView>,
+    /// Float image the path tracer sums its samples into, kept across frames.
+    pub accumulation: Arc<ImageView>,
+    /// Ping-pong images of the denoiser, TAA and post-processing passes.
+    pub post: [Arc<ImageView>; 2],
+    /// TAA resolve of the previous frame, copied from its output.
+    pub history: Arc<ImageView>,
+    /// Bloom pyramid on the way down, starting at half resolution.
+    pub bloom_down: Vec<Arc<ImageView>>,
+    /// Bloom pyramid on the way up, one level fewer as the smallest level
+    /// is only downsampled.
+    pub bloom_up: Vec<Arc<ImageView>>,
+    /// Start distances of the cone march pre-pass levels, see
+    /// `CONE_CELL_SIZES`.
+    pub cone_starts: Vec<Arc<ImageView>>,
+    pub scene_framebuffer: Arc<Framebuffer>,
+    pub post_framebuffers: [Arc<Framebuffer>; 2],
+    pub bloom_down_framebuffers: Vec<Arc<Framebuffer>>,
+    pub bloom_up_framebuffers: Vec<Arc<Framebuffer>>,
+}
+
+impl RenderTargets {
+    pub fn new(
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        image_extent: [u32; 2],
+        scene_render_pass: &Arc<RenderPass>,
+        post_render_pass: &Arc<RenderPass>,
+        backend: Backend,
+    ) -> RenderTargets {
+        let attachment_usage = ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED;
+        // The compute ray marcher stores its outputs instead of rendering
+        // them.
+        let scene_usage = match backend {
+            Backend::Graphics => attachment_usage,
+            Backend::Compute => attachment_usage | ImageUsage::STORAGE,
+        };
+
+        let color = create_image(memory_allocator, image_extent, SCENE_COLOR_FORMAT, scene_usage);
+        let normal_depth = create_image(memory_allocator, image_extent, SCENE_COLOR_FORMAT, scene_usage);
+        let albedo = create_image(memory_allocator, image_extent, ALBEDO_FORMAT, scene_usage);
+        let accumulation = create_image(
+            memory_allocator,
+            image_extent,
+            Format::R32G32B32A32_SFLOAT,
+            ImageUsage::STORAGE,
+        );
+        // The TAA's output is copied to the history.
+        let post_usage = attachment_usage | ImageUsage::TRANSFER_SRC;
+        let post = [
+            create_image(memory_allocator, image_extent, SCENE_COLOR_FORMAT, post_usage),
+            create_image(memory_allocator, image_extent, SCENE_COLOR_FORMAT, post_usage),
+        ];
+        let history = create_image(
+            memory_allocator,
+            image_extent,
+            SCENE_COLOR_FORMAT,
+            ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+        );
+
+        let bloom_extent = |level: usize| image_extent.map(|size| (size >> (level + 1)).max(1));
+        let bloom_down: Vec<_> = (0..BLOOM_LEVELS)
+            .map(|level| {
+                create_image(memory_allocator, bloom_extent(level), SCENE_COLOR_FORMAT, attachment_usage)
+            })
+            .collect();
+        let bloom_up: Vec<_> = (0..BLOOM_LEVELS - 1)
+            .map(|level| {
+                create_image(memory_allocator, bloom_extent(level), SCENE_COLOR_FORMAT, attachment_usage)
+            })
+            .collect();
+
+        let cone_starts = CONE_CELL_SIZES
+            .iter()
+            .map(|&cell_size| {
+                create_image(
+                    memory_allocator,
+                    image_extent.map(|size| size.div_ceil(cell_size)),
+                    Format::R32_SFLOAT,
+                    ImageUsage::STORAGE | ImageUsage::SAMPLED,
+                )
+            })
+            .collect();
+
+        let scene_framebuffer = create_framebuffer(
+            scene_render_pass,
+            vec![color.clone(), normal_depth.clone(), albedo.clone()],
+        );
+        let post_framebuffers = [
+            create_framebuffer(post_render_pass, vec![post[0].clone()]),
+            create_framebuffer(post_render_pass, vec![post[1].clone()]),
+        ];
+        let bloom_down_framebuffers = bloom_down
+            .iter()
+            .map(|image| create_framebuffer(post_render_pass, vec![image.clone()]))
+            .collect();
+        let bloom_up_framebuffers = bloom_up
+            .iter()
+            .map(|image| create_framebuffer(post_render_pass, vec![image.clone()]))
+            .collect();
+
+        RenderTargets {
+            color,
+            normal_depth,
+            albedo,
+            accumulation,
+            post,
+            history,
+            bloom_down,
+            bloom_up,
+            cone_starts,
+            scene_framebuffer,
+            post_framebuffers,
+            bloom_down_framebuffers,
+            bloom_up_framebuffers,
+        }
+    }
+}
+
+/// Creates the start distances the coarsest cone march level starts from, a
+/// single texel of zero.
+pub fn create_cone_placeholder(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+) -> Arc<ImageView> {
+    let placeholder = create_image(
+        memory_allocator,
+        [1, 1],
+        Format::R32_SFLOAT,
+        ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+    );
+
+    command_buffers::submit_and_wait(command_buffer_allocator, queue, |builder| {
+        builder
+            .clear_color_image(ClearColorImageInfo::image(placeholder.image().clone()))
+            .unwrap();
+    });
+
+    placeholder
+}
+
+fn create_image(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    image_extent: [u32; 2],
+    format: Format,
+    usage: ImageUsage,
+) -> Arc<ImageView> {
+    let image = Image::new(
+        memory_allocator.clone(),
+        ImageCreateInfo {
+            format,
+            extent: [image_extent[0], image_extent[1], 1],
+            usage,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    ).expect("Could not create a render target image.");
+
+    ImageView::new_default(image).unwrap()
+}
+
+fn create_framebuffer(
+    render_pass: &Arc<RenderPass>,
+    attachments: Vec<Arc<ImageView>>,
+) -> Arc<Framebuffer> {
+    Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
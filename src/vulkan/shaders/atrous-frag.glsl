@@ -0,0 +1,62 @@
+#version 460
+
+// One iteration of the edge-avoiding a-trous wavelet filter, see Dammertz et
+// al., "Edge-Avoiding A-Trous Wavelet Transform for fast Global Illumination
+// Filtering".
+
+layout(origin_upper_left) in vec4 gl_FragCoord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform constants {
+	int stepWidth;
+	float colorPhi;
+	float normalPhi;
+	float depthPhi;
+	float albedoPhi;
+} PushConstants;
+
+layout(set = 0, binding = 0) uniform sampler2D colorImage;
+layout(set = 0, binding = 1) uniform sampler2D normalDepthImage;
+layout(set = 0, binding = 2) uniform sampler2D albedoImage;
+
+// 1D B3 spline kernel, applied separably over a 5x5 footprint.
+const float KERNEL[3] = float[](3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0);
+
+void main() {
+	ivec2 pixel = ivec2(gl_FragCoord.xy);
+	ivec2 size = textureSize(colorImage, 0);
+
+	vec4 color = texelFetch(colorImage, pixel, 0);
+	vec4 normalDepth = texelFetch(normalDepthImage, pixel, 0);
+	vec3 albedo = texelFetch(albedoImage, pixel, 0).rgb;
+
+	vec3 sum = vec3(0.0);
+	float weightSum = 0.0;
+
+	for(int y = -2; y <= 2; y++) {
+		for(int x = -2; x <= 2; x++) {
+			ivec2 q = clamp(pixel + ivec2(x, y) * PushConstants.stepWidth, ivec2(0), size - 1);
+
+			vec3 qColor = texelFetch(colorImage, q, 0).rgb;
+			vec4 qNormalDepth = texelFetch(normalDepthImage, q, 0);
+			vec3 qAlbedo = texelFetch(albedoImage, q, 0).rgb;
+
+			vec3 colorDiff = color.rgb - qColor;
+			vec3 normalDiff = normalDepth.xyz - qNormalDepth.xyz;
+			vec3 albedoDiff = albedo - qAlbedo;
+			float depthDiff = abs(normalDepth.w - qNormalDepth.w);
+
+			float weight = KERNEL[abs(x)] * KERNEL[abs(y)]
+				* exp(-dot(colorDiff, colorDiff) / PushConstants.colorPhi)
+				* exp(-dot(normalDiff, normalDiff) / PushConstants.normalPhi)
+				* exp(-depthDiff / PushConstants.depthPhi)
+				* exp(-dot(albedoDiff, albedoDiff) / PushConstants.albedoPhi);
+
+			sum += qColor * weight;
+			weightSum += weight;
+		}
+	}
+
+	f_color = vec4(sum / max(weightSum, 1e-6), color.a);
+}
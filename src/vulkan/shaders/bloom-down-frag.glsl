@@ -0,0 +1,77 @@
+#version 460
+
+// One level of the bloom pyramid, downsampling the level above with a 13-tap
+// filter, see Jimenez, "Next Generation Post Processing in Call of Duty:
+// Advanced Warfare".
+
+layout(origin_upper_left) in vec4 gl_FragCoord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform constants {
+	uint firstLevel; // Whether the source is the full resolution image.
+	float threshold;
+} PushConstants;
+
+layout(set = 0, binding = 0) uniform sampler2D sourceImage;
+
+// Width of the soft transition around the threshold.
+const float KNEE = 0.5;
+
+float luminance(vec3 color) {
+	return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Keeps the part of the color above the threshold, with a quadratic knee.
+vec3 applyThreshold(vec3 color) {
+	float brightness = max(color.r, max(color.g, color.b));
+	float soft = clamp(brightness - PushConstants.threshold + KNEE, 0.0, 2.0 * KNEE);
+	soft = soft * soft / (4.0 * KNEE);
+	return color * max(soft, brightness - PushConstants.threshold) / max(brightness, 1e-5);
+}
+
+void main() {
+	ivec2 sourceSize = textureSize(sourceImage, 0);
+	vec2 texel = 1.0 / vec2(sourceSize);
+	vec2 uv = gl_FragCoord.xy / vec2(max(sourceSize / 2, ivec2(1)));
+
+	vec3 a = texture(sourceImage, uv + texel * vec2(-2.0, -2.0)).rgb;
+	vec3 b = texture(sourceImage, uv + texel * vec2(0.0, -2.0)).rgb;
+	vec3 c = texture(sourceImage, uv + texel * vec2(2.0, -2.0)).rgb;
+	vec3 d = texture(sourceImage, uv + texel * vec2(-2.0, 0.0)).rgb;
+	vec3 e = texture(sourceImage, uv).rgb;
+	vec3 f = texture(sourceImage, uv + texel * vec2(2.0, 0.0)).rgb;
+	vec3 g = texture(sourceImage, uv + texel * vec2(-2.0, 2.0)).rgb;
+	vec3 h = texture(sourceImage, uv + texel * vec2(0.0, 2.0)).rgb;
+	vec3 i = texture(sourceImage, uv + texel * vec2(2.0, 2.0)).rgb;
+	vec3 j = texture(sourceImage, uv + texel * vec2(-1.0, -1.0)).rgb;
+	vec3 k = texture(sourceImage, uv + texel * vec2(1.0, -1.0)).rgb;
+	vec3 l = texture(sourceImage, uv + texel * vec2(-1.0, 1.0)).rgb;
+	vec3 m = texture(sourceImage, uv + texel * vec2(1.0, 1.0)).rgb;
+
+	// Overlapping 2x2 boxes, the center one weighing as much as the others.
+	vec3 boxes[5] = vec3[](
+		(j + k + l + m) * 0.25,
+		(a + b + d + e) * 0.25,
+		(b + c + e + f) * 0.25,
+		(d + e + g + h) * 0.25,
+		(e + f + h + i) * 0.25);
+	float weights[5] = float[](0.5, 0.125, 0.125, 0.125, 0.125);
+
+	vec3 color = vec3(0.0);
+	float totalWeight = 0.0;
+	for(int box = 0; box < 5; box++) {
+		vec3 boxColor = boxes[box];
+		float weight = weights[box];
+		if(PushConstants.firstLevel != 0) {
+			// Karis average, so single bright pixels of the path tracer
+			// do not flicker into large blobs.
+			boxColor = applyThreshold(boxColor);
+			weight /= 1.0 + luminance(boxColor);
+		}
+		color += boxColor * weight;
+		totalWeight += weight;
+	}
+
+	f_color = vec4(color / totalWeight, 1.0);
+}
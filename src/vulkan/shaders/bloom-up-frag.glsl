@@ -0,0 +1,31 @@
+#version 460
+
+// One level of the bloom pyramid on the way up: the level below, upsampled
+// with a 3x3 tent filter, added to this level's downsampled image.
+
+layout(origin_upper_left) in vec4 gl_FragCoord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform constants {
+	float radius; // In texels of the level below.
+} PushConstants;
+
+layout(set = 0, binding = 0) uniform sampler2D lowerImage;
+layout(set = 0, binding = 1) uniform sampler2D levelImage;
+
+void main() {
+	ivec2 pixel = ivec2(gl_FragCoord.xy);
+	vec2 uv = gl_FragCoord.xy / vec2(textureSize(levelImage, 0));
+	vec2 texel = PushConstants.radius / vec2(textureSize(lowerImage, 0));
+
+	vec3 upsampled = vec3(0.0);
+	for(int y = -1; y <= 1; y++) {
+		for(int x = -1; x <= 1; x++) {
+			float weight = float((2 - abs(x)) * (2 - abs(y))) / 16.0;
+			upsampled += texture(lowerImage, uv + texel * vec2(x, y)).rgb * weight;
+		}
+	}
+
+	f_color = vec4(texelFetch(levelImage, pixel, 0).rgb + upsampled, 1.0);
+}
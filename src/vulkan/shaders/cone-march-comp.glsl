@@ -0,0 +1,52 @@
+#version 460
+
+// Cone marches the scene at a fraction of the resolution, one cone per texel
+// enclosing the rays of all its pixels. Each texel stores the distance up to
+// which the cone is free of surfaces, where those rays can start marching.
+
+layout(local_size_x = 8, local_size_y = 8) in;
+
+// Pixels per texel of this level, specialized for each level. Must match
+// render_targets.rs.
+layout(constant_id = 3) const int CELL_SIZE = 8;
+
+layout(set = 1, binding = 0, r32f) uniform writeonly image2D startImage;
+
+// Start distances of the coarser level, twice the cell size, which enclose
+// the cones of this one. A 1x1 image of zero for the coarsest level.
+layout(set = 1, binding = 1) uniform sampler2D coarseStarts;
+
+#include "ray-marcher.glsl"
+
+void main() {
+	ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+	if(any(greaterThanEqual(texel, imageSize(startImage)))) {
+		return;
+	}
+
+	// The axis goes through the exact center of the cell, from its first
+	// pixel's corner.
+	fragCoord = vec2(texel) * float(CELL_SIZE);
+	generateRay(vec2(CELL_SIZE) * 0.5);
+	// Radius of the cone per unit of distance from the camera, reaching the
+	// corners of the cell widened by the TAA jitter of up to half a pixel.
+	// pixelCone is half a pixel per unit of distance.
+	float coneRadius = (float(CELL_SIZE) * 0.5 + 0.5) * sqrt(2.0) * 2.0 * pixelCone;
+	float start = distance(ray.pos, frame.cameraPosition);
+
+	ivec2 coarseTexel = min(texel / 2, textureSize(coarseStarts, 0) - 1);
+	float t = texelFetch(coarseStarts, coarseTexel, 0).r;
+
+	for(int i = 0; i < MAX_ITER && t < MAX_DIST; i++) {
+		float dist = sdScene(ray.pos + ray.dir * t).x;
+		// The free sphere around the axis encloses the cone until the cone's
+		// radius catches up with it.
+		float step = dist - coneRadius * (start + t + dist);
+		if(step < MIN_DIST) {
+			break;
+		}
+		t += step;
+	}
+
+	imageStore(startImage, texel, vec4(t));
+}
@@ -0,0 +1,81 @@
+#version 460
+
+// Averages the luminance histogram into the scene luminance the tone mapping
+// pass exposes for, adapting to it over time.
+
+layout(local_size_x = 256) in;
+
+layout(std430, set = 0, binding = 0) buffer Histogram {
+	uint bins[256];
+};
+
+layout(std430, set = 0, binding = 1) buffer Exposure {
+	float averageLuminance; // 0 until the first frame is measured.
+};
+
+// Must match tonemap-frag.glsl.
+layout(set = 0, binding = 2) uniform ToneMapSettings {
+	uint toneMapper;
+	uint autoExposure;
+	float exposure;
+	float adaptation;
+	vec3 lutDomainMin;
+	float lutStrength;
+	vec3 lutDomainMax;
+	uint outputEncoding;
+	uint upscaleFilter;
+} settings;
+
+// Must match histogram-comp.glsl.
+const float MIN_LOG_LUMINANCE = -10.0;
+const float LOG_LUMINANCE_RANGE = 20.0;
+
+// Fractions of the pixels ignored at the dark and bright ends, so small
+// highlights and shadows do not swing the exposure.
+const float LOW_PERCENTILE = 0.4;
+const float HIGH_PERCENTILE = 0.95;
+
+shared uint localBins[256];
+
+float binLogLuminance(uint bin) {
+	return (float(bin) - 0.5) / 254.0 * LOG_LUMINANCE_RANGE + MIN_LOG_LUMINANCE;
+}
+
+void main() {
+	uint i = gl_LocalInvocationIndex;
+	localBins[i] = bins[i];
+	bins[i] = 0;
+	barrier();
+
+	if(i != 0) {
+		return;
+	}
+
+	uint total = 0;
+	for(uint bin = 1; bin < 256; bin++) {
+		total += localBins[bin];
+	}
+	if(total == 0) {
+		return;
+	}
+
+	float low = LOW_PERCENTILE * float(total);
+	float high = HIGH_PERCENTILE * float(total);
+	float seen = 0.0;
+	float sum = 0.0;
+	float weight = 0.0;
+	for(uint bin = 1; bin < 256; bin++) {
+		// Part of the bin's pixels between the two percentiles.
+		float count = float(localBins[bin]);
+		float inside = clamp(seen + count, low, high) - clamp(seen, low, high);
+		seen += count;
+
+		sum += inside * binLogLuminance(bin);
+		weight += inside;
+	}
+
+	float target = exp2(sum / max(weight, 1.0));
+	averageLuminance = averageLuminance > 0.0
+		? mix(averageLuminance, target, settings.adaptation)
+		: target;
+}
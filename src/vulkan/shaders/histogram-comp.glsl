@@ -0,0 +1,43 @@
+#version 460
+
+// Counts the pixels of the rendered image in bins of log luminance, for the
+// auto exposure.
+
+layout(local_size_x = 16, local_size_y = 16) in;
+
+layout(set = 0, binding = 0) uniform sampler2D colorImage;
+
+// Cleared by exposure-comp.glsl once read.
+layout(std430, set = 0, binding = 1) buffer Histogram {
+	uint bins[256];
+};
+
+// Must match exposure-comp.glsl. Bin 0 holds the black pixels, the others
+// span the log luminance range.
+const float MIN_LOG_LUMINANCE = -10.0;
+const float LOG_LUMINANCE_RANGE = 20.0;
+
+shared uint localBins[256];
+
+uint luminanceBin(vec3 color) {
+	float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+	if(luminance < 1e-5) {
+		return 0;
+	}
+
+	float t = clamp((log2(luminance) - MIN_LOG_LUMINANCE) / LOG_LUMINANCE_RANGE, 0.0, 1.0);
+	return uint(t * 254.0) + 1;
+}
+
+void main() {
+	localBins[gl_LocalInvocationIndex] = 0;
+	barrier();
+
+	ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+	if(all(lessThan(pixel, textureSize(colorImage, 0)))) {
+		atomicAdd(localBins[luminanceBin(texelFetch(colorImage, pixel, 0).rgb)], 1);
+	}
+	barrier();
+
+	atomicAdd(bins[gl_LocalInvocationIndex], localBins[gl_LocalInvocationIndex]);
+}
@@ -11,3 +11,73 @@ pub mod fs {
         path: "src/vulkan/shaders/ray-marcher-frag.glsl",
     }
 }
+
+pub mod cs {
+    vulkano_shaders::shader!{
+        ty: "compute",
+        path: "src/vulkan/shaders/ray-marcher-comp.glsl",
+    }
+}
+
+pub mod cone_cs {
+    vulkano_shaders::shader!{
+        ty: "compute",
+        path: "src/vulkan/shaders/cone-march-comp.glsl",
+    }
+}
+
+pub mod taa_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/vulkan/shaders/taa-frag.glsl",
+    }
+}
+
+pub mod atrous_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/vulkan/shaders/atrous-frag.glsl",
+    }
+}
+
+pub mod bloom_down_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/vulkan/shaders/bloom-down-frag.glsl",
+    }
+}
+
+pub mod bloom_up_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/vulkan/shaders/bloom-up-frag.glsl",
+    }
+}
+
+pub mod post_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/vulkan/shaders/post-frag.glsl",
+    }
+}
+
+pub mod histogram_cs {
+    vulkano_shaders::shader!{
+        ty: "compute",
+        path: "src/vulkan/shaders/histogram-comp.glsl",
+    }
+}
+
+pub mod exposure_cs {
+    vulkano_shaders::shader!{
+        ty: "compute",
+        path: "src/vulkan/shaders/exposure-comp.glsl",
+    }
+}
+
+pub mod tonemap_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/vulkan/shaders/tonemap-frag.glsl",
+    }
+}
@@ -0,0 +1,71 @@
+#version 460
+
+// One effect of the post-processing chain, applied to the HDR image before
+// tone mapping.
+
+layout(origin_upper_left) in vec4 gl_FragCoord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform constants {
+	uint effect;
+	float strength;
+} PushConstants;
+
+layout(set = 0, binding = 0) uniform sampler2D colorImage;
+
+// Top of the bloom pyramid, only read by the bloom effect.
+layout(set = 0, binding = 1) uniform sampler2D bloomImage;
+
+// Prefix of FrameData in ray-marcher.glsl.
+layout(set = 0, binding = 2) uniform FrameData {
+	vec3 cameraPosition;
+	uint sampleCount;
+	vec3 cameraRight;
+	uint frameIndex;
+} frame;
+
+const uint EFFECT_BLOOM = 0;
+const uint EFFECT_VIGNETTE = 1;
+const uint EFFECT_CHROMATIC_ABERRATION = 2;
+const uint EFFECT_FILM_GRAIN = 3;
+const uint EFFECT_SHARPEN = 4;
+
+// PCG hash, like ray-marcher.glsl's.
+uint pcgHash(uint v) {
+	uint state = v * 747796405u + 2891336453u;
+	uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+	return (word >> 22u) ^ word;
+}
+
+vec3 fetch(ivec2 pixel) {
+	return texelFetch(colorImage, clamp(pixel, ivec2(0), textureSize(colorImage, 0) - 1), 0).rgb;
+}
+
+void main() {
+	ivec2 pixel = ivec2(gl_FragCoord.xy);
+	vec2 uv = gl_FragCoord.xy / vec2(textureSize(colorImage, 0));
+	vec3 color = fetch(pixel);
+	float strength = PushConstants.strength;
+
+	if(PushConstants.effect == EFFECT_BLOOM) {
+		color += strength * texture(bloomImage, uv).rgb;
+	} else if(PushConstants.effect == EFFECT_VIGNETTE) {
+		vec2 q = uv * (1.0 - uv);
+		color *= pow(16.0 * q.x * q.y, strength);
+	} else if(PushConstants.effect == EFFECT_CHROMATIC_ABERRATION) {
+		vec2 offset = (uv - 0.5) * 2.0 * strength;
+		color.r = texture(colorImage, uv - offset).r;
+		color.b = texture(colorImage, uv + offset).b;
+	} else if(PushConstants.effect == EFFECT_FILM_GRAIN) {
+		uint hash = pcgHash(uint(pixel.x) + pcgHash(uint(pixel.y) + pcgHash(frame.frameIndex)));
+		float noise = float(hash) / 4294967296.0 - 0.5;
+		color *= max(1.0 + 2.0 * strength * noise, 0.0);
+	} else if(PushConstants.effect == EFFECT_SHARPEN) {
+		vec3 neighbors = fetch(pixel + ivec2(1, 0)) + fetch(pixel - ivec2(1, 0))
+			+ fetch(pixel + ivec2(0, 1)) + fetch(pixel - ivec2(0, 1));
+		color = max(color + strength * (4.0 * color - neighbors), 0.0);
+	}
+
+	f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,30 @@
+#version 460
+
+// Compute entry point of the ray marcher, one invocation per pixel in tiles
+// of 8x8 pixels.
+
+layout(local_size_x = 8, local_size_y = 8) in;
+
+// Render targets, in the formats of render_pass.rs.
+layout(set = 1, binding = 0, rgba32f) uniform writeonly image2D colorImage;
+layout(set = 1, binding = 1, rgba32f) uniform writeonly image2D normalDepthImage;
+layout(set = 1, binding = 2, rgba8) uniform writeonly image2D albedoImage;
+
+#include "ray-marcher.glsl"
+
+void main() {
+	ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+	if(any(greaterThanEqual(pixel, ivec2(PushConstants.windowSize)))) {
+		return;
+	}
+
+	fragCoord = vec2(pixel) + 0.5;
+	vec4 color;
+	vec4 normalDepth;
+	vec4 albedo;
+	render(color, normalDepth, albedo);
+
+	imageStore(colorImage, pixel, color);
+	imageStore(normalDepthImage, pixel, normalDepth);
+	imageStore(albedoImage, pixel, albedo);
+}
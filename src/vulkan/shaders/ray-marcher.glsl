@@ -0,0 +1,1508 @@
+// Ray marcher shared by the fragment and compute shader backends, included by
+// ray-marcher-frag.glsl and ray-marcher-comp.glsl which call render() for
+// every pixel.
+
+layout(push_constant) uniform constants {
+	vec2 windowSize;
+	float fov;
+	float nearPlane;
+	uint debugView;
+} PushConstants;
+
+// Debug views, replacing the shading. Must match tonemap-frag.glsl.
+const uint DEBUG_OFF = 0;
+const uint DEBUG_ITERATIONS = 1;
+const uint DEBUG_DEPTH = 2;
+const uint DEBUG_NORMALS = 3;
+const uint DEBUG_MATERIALS = 4;
+const uint DEBUG_STEP_HISTOGRAM = 5;
+
+const uint MAX_LIGHTS = 8;
+const uint LIGHT_DIRECTIONAL = 0;
+const uint LIGHT_POINT = 1;
+
+struct Light {
+	vec3 position; // Direction the light travels in for directional lights.
+	uint kind;
+	vec3 color;
+	float intensity;
+};
+
+layout(set = 0, binding = 0) uniform SceneData {
+	Light lights[MAX_LIGHTS];
+	vec3 ambient;
+	uint lightCount;
+	vec3 skyZenith;
+	uint environmentMapLevels; // 0 when the procedural sky is used.
+	vec3 skyHorizon;
+	float atmosphereTurbidity;
+	vec3 skyGround;
+	uint atmosphere; // Whether the sky is the scattering model.
+	vec3 fogAlbedo;
+	float fogDensity; // 0 without fog.
+	float fogFalloff;
+	float fogHeight;
+	float fogAnisotropy;
+	uint volumeCount;
+	float cloudBottom;
+	float cloudTop;
+	float cloudCoverage;
+	float cloudDensity; // 0 without clouds.
+	vec3 cloudWind;
+	float cloudScale;
+	float cloudDetailScale;
+	float cloudForwardScattering;
+	float cloudBackScattering;
+	float padding0;
+} scene;
+
+const uint SHAPE_SPHERE = 0;
+const uint SHAPE_BOX = 1;
+const uint SHAPE_TORUS = 2;
+const uint SHAPE_PLANE = 3;
+
+struct Node {
+	vec3 position;
+	uint shape;
+	vec4 params;
+	vec3 repeat;
+	uint material;
+};
+
+layout(std430, set = 0, binding = 2) readonly buffer Nodes {
+	Node nodes[];
+};
+
+struct Material {
+	vec3 albedo;
+	float roughness;
+	vec3 emissive;
+	float metallic;
+	float specular;
+	float reflectivity;
+	float transmission;
+	float ior;
+};
+
+layout(std430, set = 0, binding = 3) readonly buffer Materials {
+	Material materials[];
+};
+
+layout(set = 0, binding = 1) uniform Settings {
+	uint shadows;
+	float shadowSoftness;
+	uint ambientOcclusion;
+	uint aoSamples;
+	float aoStepSize;
+	float aoStrength;
+	uint shadingModel;
+	uint maxBounces;
+	uint renderMode;
+	float environmentRotation;
+	float environmentIntensity;
+	uint volumetrics;
+	uint volumeSteps;
+	uint clouds;
+	uint cloudSteps;
+	uint cloudLightSteps;
+	uint footprintLod; // Scales the hit threshold and noise detail with distance.
+	float relaxation; // Over-relaxation factor of the march, 1 for plain steps.
+	uint conePrepass; // Primary rays start at the cone march pre-pass's distances.
+	uint ssaaGridSize; // Supersamples per pixel along each axis, 1 without.
+	uint ssaaPattern;
+	uint ssaaFilter;
+	uint marchStats; // Counts the primary rays' iterations outside the debug views.
+} settings;
+
+const uint SHADING_BLINN_PHONG = 0;
+const uint SHADING_PBR = 1;
+
+const uint RENDER_DIRECT = 0;
+const uint RENDER_PATH_TRACED = 1;
+
+const uint SAMPLE_GRID = 0;
+const uint SAMPLE_STRATIFIED = 1;
+const uint SAMPLE_ROTATED_GRID = 2;
+
+const uint FILTER_BOX = 0;
+const uint FILTER_GAUSSIAN = 1;
+const uint FILTER_MITCHELL = 2;
+
+layout(set = 0, binding = 4) uniform FrameData {
+	vec3 cameraPosition;
+	uint sampleCount;
+	vec3 cameraRight;
+	uint frameIndex;
+	vec3 cameraUp;
+	float padding0;
+	vec3 cameraForward;
+	float padding1;
+	vec3 sunDirection; // Toward the sun.
+	float sunIntensity; // 0 without an atmosphere.
+	vec3 sunColor;
+	float time; // Seconds since startup, drives the cloud wind.
+	vec2 jitter; // Subpixel offset of the primary rays for the TAA, in pixels.
+} frame;
+
+// Running sum of the path traced samples, reset when sampleCount is 0.
+layout(set = 0, binding = 5, rgba32f) uniform image2D accumulation;
+
+// Equirectangular environment map with a mip chain, blurrier levels standing
+// in for a prefiltered map.
+layout(set = 0, binding = 6) uniform sampler2D environmentMap;
+
+const uint VOLUME_CONSTANT = 0;
+const uint VOLUME_NOISE = 1;
+const uint VOLUME_TEXTURE = 2;
+
+struct Volume {
+	vec3 position;
+	uint densityType;
+	vec3 size; // Half extents of the volume's box.
+	float density;
+	vec3 albedo;
+	float anisotropy;
+	float noiseScale;
+	uint noiseOctaves;
+	float padding0;
+	float padding1;
+};
+
+layout(std430, set = 0, binding = 8) readonly buffer Volumes {
+	Volume volumes[];
+};
+
+// Density grid of the volume with a VOLUME_TEXTURE density, spanning its box.
+layout(set = 0, binding = 7) uniform sampler3D volumeTexture;
+
+// Tileable cloud noise: Perlin-Worley in red for the base shape, Worley noise
+// of increasing frequency in green, blue and alpha for erosion.
+layout(set = 0, binding = 9) uniform sampler3D cloudNoise;
+
+// Step sizes of the primary rays in the step histogram debug view, on a log2
+// scale. Must match tonemap-frag.glsl.
+const uint STEP_BINS = 32;
+const float STEP_LOG_MIN = -10.0;
+const float STEP_LOG_RANGE = 17.0;
+
+layout(std430, set = 0, binding = 10) buffer StepHistogram {
+	uint stepBins[STEP_BINS];
+};
+
+// Distances primary rays can safely start marching at, written by the finest
+// level of cone-march-comp.glsl.
+layout(set = 0, binding = 12) uniform sampler2D coneStarts;
+
+// Pixels per texel of coneStarts. Must match render_targets.rs.
+const int CONE_CELL_SIZE = 4;
+
+// Iterations of the primary rays in the iteration debug view or with
+// settings.marchStats, read back to report the savings of the over-relaxation
+// over plain sphere tracing.
+layout(std430, set = 0, binding = 11) buffer MarchStats {
+	uint rayCount;
+	uint iterations;
+	uint plainIterations;
+};
+
+const float PI = 3.14159265359;
+
+struct Ray {
+	vec3 dir;
+	vec3 pos;
+} ray;
+
+uint rngState;
+
+// Center of the pixel being rendered, like gl_FragCoord.xy.
+vec2 fragCoord;
+
+// Radius of the pixel's footprint per unit of distance from the camera, set
+// by generateRay.
+float pixelCone;
+
+// PCG hash, see https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/
+uint pcgHash(uint v) {
+	uint state = v * 747796405u + 2891336453u;
+	uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+	return (word >> 22u) ^ word;
+}
+
+void initRandom() {
+	uvec2 pixel = uvec2(fragCoord);
+	rngState = pcgHash(pixel.x + pcgHash(pixel.y + pcgHash(frame.frameIndex)));
+}
+
+float random() {
+	rngState = pcgHash(rngState);
+	return float(rngState) / 4294967296.0;
+}
+
+// Generates the ray through the given position in the pixel, (0.5, 0.5)
+// being its center.
+void generateRay(vec2 subpixel) {
+	float halfWidth = tan(radians(PushConstants.fov / 2)) * PushConstants.nearPlane;
+	float halfHeight = halfWidth * PushConstants.windowSize.y / PushConstants.windowSize.x;
+	float pixelSize = 2 * halfWidth / PushConstants.windowSize.x;
+	pixelCone = 0.5 * pixelSize / PushConstants.nearPlane;
+
+	vec2 position = floor(fragCoord) + subpixel;
+	vec2 offset = vec2(1.0, -1.0) * (position * pixelSize - vec2(halfWidth, halfHeight));
+
+	vec3 onPlane = offset.x * frame.cameraRight
+		+ offset.y * frame.cameraUp
+		+ PushConstants.nearPlane * frame.cameraForward;
+
+	ray.pos = frame.cameraPosition + onPlane;
+	ray.dir = normalize(onPlane);
+}
+
+// March limits of the quality preset, specialized when the pipeline is
+// created, see pipeline.rs.
+layout(constant_id = 0) const int MAX_ITER = 512;
+layout(constant_id = 1) const float MAX_DIST = 100.0;
+layout(constant_id = 2) const float MIN_DIST = 0.001;
+
+// Distance along the ray the next march starts at, reset by the march.
+float marchStart = 0.0;
+
+// Hit threshold at the end of the last march. Normals are estimated and
+// rays leave the surface at this scale.
+float marchEpsilon;
+
+// Radius of the pixel's footprint at the given distance from the camera.
+float footprint(float distance) {
+	return distance * pixelCone;
+}
+
+// Hit threshold of a march at the given distance from the camera. Surfaces
+// are refined down to the pixel's footprint rather than a fixed distance,
+// which would waste iterations and alias far away.
+float hitEpsilon(float distance) {
+	if(settings.footprintLod == 0) {
+		return MIN_DIST;
+	}
+	return max(MIN_DIST, footprint(distance));
+}
+
+float sdSphere(vec3 p, float r) {
+	return length(p) - r;
+}
+
+float sdBox(vec3 p, vec3 size, float rounding) {
+	vec3 q = abs(p) - size + rounding;
+	return length(max(q, 0.0)) + min(max(q.x, max(q.y, q.z)), 0.0) - rounding;
+}
+
+float sdTorus(vec3 p, float majorRadius, float minorRadius) {
+	vec2 q = vec2(length(p.xz) - majorRadius, p.y);
+	return length(q) - minorRadius;
+}
+
+float sdPlane(vec3 p, vec3 normal) {
+	return dot(p, normalize(normal));
+}
+
+float sdNode(vec3 p, Node node) {
+	vec3 q = p - node.position;
+	vec3 cell = max(node.repeat, vec3(0.0));
+	q = mix(q, q - cell * round(q / max(cell, vec3(1e-6))), greaterThan(cell, vec3(0.0)));
+
+	switch(node.shape) {
+	case SHAPE_SPHERE:
+		return sdSphere(q, node.params.x);
+	case SHAPE_BOX:
+		return sdBox(q, node.params.xyz, node.params.w);
+	case SHAPE_TORUS:
+		return sdTorus(q, node.params.x, node.params.y);
+	case SHAPE_PLANE:
+		return sdPlane(q, node.params.xyz);
+	}
+
+	return MAX_DIST;
+}
+
+// Returns the distance to the closest node and that node's material ID.
+vec2 sdScene(vec3 p) {
+	vec2 res = vec2(MAX_DIST * 2.0, 0.0);
+
+	for(int i = 0; i < nodes.length(); i++) {
+		float dist = sdNode(p, nodes[i]);
+		if(dist < res.x) {
+			res = vec2(dist, float(nodes[i].material));
+		}
+	}
+
+	return res;
+}
+
+// Tetrahedral gradient estimate, four scene evaluations instead of six.
+vec3 calcNormal(vec3 p) {
+	const vec2 k = vec2(1.0, -1.0);
+	float h = marchEpsilon;
+	return normalize(
+		k.xyy * sdScene(p + k.xyy * h).x +
+		k.yyx * sdScene(p + k.yyx * h).x +
+		k.yxy * sdScene(p + k.yxy * h).x +
+		k.xxx * sdScene(p + k.xxx * h).x
+	);
+}
+
+// Penumbra estimate from the closest miss along a secondary march toward the
+// light, see https://iquilezles.org/articles/rmshadows/
+float calcSoftShadow(vec3 origin, vec3 dir, float maxDist) {
+	float res = 1.0;
+	float t = MIN_DIST * 10.0;
+
+	for(int i = 0; i < MAX_ITER && t < maxDist; i++) {
+		float dist = sdScene(origin + dir * t).x;
+		if(dist < MIN_DIST) {
+			return 0.0;
+		}
+
+		res = min(res, settings.shadowSoftness * dist / t);
+		t += dist;
+	}
+
+	return clamp(res, 0.0, 1.0);
+}
+
+float calcAO(vec3 p, vec3 n) {
+	float occlusion = 0.0;
+	float weight = 1.0;
+
+	for(uint i = 1; i <= settings.aoSamples; i++) {
+		float h = settings.aoStepSize * float(i);
+		occlusion += weight * (h - sdScene(p + n * h).x);
+		weight *= 0.5;
+	}
+
+	return clamp(1.0 - settings.aoStrength * occlusion, 0.0, 1.0);
+}
+
+// Number of lights to evaluate, the atmosphere's sun following the scene's.
+uint lightCount() {
+	return min(scene.lightCount, MAX_LIGHTS) + (frame.sunIntensity > 0.0 ? 1 : 0);
+}
+
+Light getLight(uint i) {
+	if(i < min(scene.lightCount, MAX_LIGHTS)) {
+		return scene.lights[i];
+	}
+	return Light(-frame.sunDirection, LIGHT_DIRECTIONAL, frame.sunColor, frame.sunIntensity);
+}
+
+// Radiance arriving at p from the given light, ignoring occluders.
+vec3 lightIncident(Light light, vec3 p, out vec3 l, out float lightDist) {
+	vec3 radiance = light.color * light.intensity;
+	if(light.kind == LIGHT_DIRECTIONAL) {
+		l = normalize(-light.position);
+		lightDist = MAX_DIST;
+	} else {
+		vec3 toLight = light.position - p;
+		lightDist = length(toLight);
+		l = toLight / lightDist;
+		radiance /= lightDist * lightDist;
+	}
+
+	return radiance;
+}
+
+// Radiance arriving at p from the given light, including its shadow.
+vec3 lightRadiance(Light light, vec3 p, vec3 n, vec3 shadowOrigin, out vec3 l) {
+	float lightDist;
+	vec3 radiance = lightIncident(light, p, l, lightDist);
+
+	if(dot(n, l) <= 0.0) {
+		return vec3(0.0);
+	}
+
+	if(settings.shadows != 0) {
+		radiance *= calcSoftShadow(shadowOrigin, l, lightDist);
+	}
+
+	return radiance;
+}
+
+// Single scattering atmosphere, see Nishita et al., "Display of the Earth
+// Taking into Account Atmospheric Scattering". Distances are in meters.
+const float EARTH_RADIUS = 6360e3;
+const float ATMOSPHERE_RADIUS = 6420e3;
+const float RAYLEIGH_HEIGHT = 8e3;
+const float MIE_HEIGHT = 1.2e3;
+const vec3 RAYLEIGH_SCATTERING = vec3(5.8e-6, 13.5e-6, 33.1e-6);
+const float MIE_SCATTERING = 21e-6;
+const float MIE_G = 0.76;
+// Sun irradiance lighting the sky, independent of the sun light's intensity.
+const float ATMOSPHERE_SUN_IRRADIANCE = 20.0;
+const uint ATMOSPHERE_SAMPLES = 12;
+const uint ATMOSPHERE_LIGHT_SAMPLES = 6;
+// Cosine of the sun's angular radius.
+const float SUN_COS_RADIUS = 0.99998;
+
+// Near and far distances along the ray to a sphere centered on the origin,
+// negative when it is missed.
+vec2 raySphere(vec3 origin, vec3 dir, float radius) {
+	float b = dot(origin, dir);
+	float c = dot(origin, origin) - radius * radius;
+	float d = b * b - c;
+	if(d < 0.0) {
+		return vec2(-1.0);
+	}
+	d = sqrt(d);
+	return vec2(-b - d, -b + d);
+}
+
+vec3 atmosphereScattering(vec3 dir) {
+	vec3 origin = vec3(0.0, EARTH_RADIUS + 1.0, 0.0);
+	vec3 sunDir = frame.sunDirection;
+
+	float rayLength = raySphere(origin, dir, ATMOSPHERE_RADIUS).y;
+	float groundDist = raySphere(origin, dir, EARTH_RADIUS).x;
+	bool hitsGround = groundDist > 0.0;
+	if(hitsGround) {
+		rayLength = groundDist;
+	}
+
+	float mu = dot(dir, sunDir);
+	float rayleighPhase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+	float g2 = MIE_G * MIE_G;
+	float miePhase = 3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + mu * mu))
+		/ ((2.0 + g2) * pow(1.0 + g2 - 2.0 * MIE_G * mu, 1.5));
+
+	float mieScattering = MIE_SCATTERING * scene.atmosphereTurbidity;
+	float segment = rayLength / float(ATMOSPHERE_SAMPLES);
+	vec2 opticalDepth = vec2(0.0); // Rayleigh and Mie.
+	vec3 rayleighSum = vec3(0.0);
+	vec3 mieSum = vec3(0.0);
+
+	for(uint i = 0; i < ATMOSPHERE_SAMPLES; i++) {
+		vec3 p = origin + dir * segment * (float(i) + 0.5);
+		float height = length(p) - EARTH_RADIUS;
+		vec2 density = exp(-height / vec2(RAYLEIGH_HEIGHT, MIE_HEIGHT)) * segment;
+		opticalDepth += density;
+
+		// Points in the planet's shadow receive no sunlight.
+		if(raySphere(p, sunDir, EARTH_RADIUS).x > 0.0) {
+			continue;
+		}
+
+		float lightSegment = raySphere(p, sunDir, ATMOSPHERE_RADIUS).y / float(ATMOSPHERE_LIGHT_SAMPLES);
+		vec2 lightDepth = vec2(0.0);
+		for(uint j = 0; j < ATMOSPHERE_LIGHT_SAMPLES; j++) {
+			vec3 q = p + sunDir * lightSegment * (float(j) + 0.5);
+			float lightHeight = length(q) - EARTH_RADIUS;
+			lightDepth += exp(-lightHeight / vec2(RAYLEIGH_HEIGHT, MIE_HEIGHT)) * lightSegment;
+		}
+
+		vec2 depth = opticalDepth + lightDepth;
+		vec3 attenuation = exp(-(RAYLEIGH_SCATTERING * depth.x + mieScattering * 1.1 * depth.y));
+		rayleighSum += attenuation * density.x;
+		mieSum += attenuation * density.y;
+	}
+
+	vec3 color = rayleighSum * RAYLEIGH_SCATTERING * rayleighPhase + mieSum * mieScattering * miePhase;
+
+	// The ground reflects the sunlight that reaches it.
+	if(hitsGround) {
+		vec3 groundTransmittance = exp(-(RAYLEIGH_SCATTERING * opticalDepth.x + mieScattering * 1.1 * opticalDepth.y));
+		color += scene.skyGround * frame.sunColor * max(sunDir.y, 0.0) / PI * groundTransmittance;
+	}
+
+	return color * ATMOSPHERE_SUN_IRRADIANCE;
+}
+
+// Radiance of the sun's disk, only added where the sun is seen directly as
+// the scene is already lit by the sun light.
+vec3 sunDisk(vec3 dir) {
+	if(scene.atmosphere == 0 || dot(dir, frame.sunDirection) < SUN_COS_RADIUS) {
+		return vec3(0.0);
+	}
+	return frame.sunColor * frame.sunIntensity * ATMOSPHERE_SUN_IRRADIANCE;
+}
+
+vec3 sampleSky(vec3 dir) {
+	if(scene.atmosphere != 0) {
+		return atmosphereScattering(dir) * settings.environmentIntensity;
+	}
+
+	vec3 color = dir.y > 0.0
+		? mix(scene.skyHorizon, scene.skyZenith, sqrt(dir.y))
+		: mix(scene.skyHorizon, scene.skyGround, sqrt(-dir.y));
+	return color * settings.environmentIntensity;
+}
+
+vec3 sampleEnvironmentMap(vec3 dir, float lod) {
+	float s = sin(settings.environmentRotation);
+	float c = cos(settings.environmentRotation);
+	dir = vec3(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+
+	vec2 uv = vec2(atan(dir.x, dir.z) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+	return textureLod(environmentMap, uv, lod).rgb * settings.environmentIntensity;
+}
+
+// Approximates a prefiltered environment lookup, by picking a blurrier mip
+// level of the map or fading toward the sky's average color as the lobe
+// widens.
+vec3 sampleEnvironment(vec3 dir, float roughness) {
+	if(scene.environmentMapLevels > 0) {
+		return sampleEnvironmentMap(dir, roughness * float(scene.environmentMapLevels - 1));
+	}
+
+	vec3 average = scene.atmosphere != 0
+		? atmosphereScattering(vec3(0.0, 1.0, 0.0))
+		: (scene.skyZenith + 2.0 * scene.skyHorizon + scene.skyGround) * 0.25;
+	return mix(sampleSky(dir), average * settings.environmentIntensity, roughness * roughness);
+}
+
+vec3 shadeBlinnPhong(vec3 p, vec3 n, vec3 v, Material material, float ao) {
+	vec3 diffuseColor = material.albedo * (1.0 - material.metallic);
+	vec3 specularColor = mix(vec3(material.specular), material.albedo, material.metallic);
+	// Blinn-Phong exponent matching the material's roughness.
+	float roughness = max(material.roughness, 0.05);
+	float shininess = 2.0 / (roughness * roughness * roughness * roughness) - 2.0;
+
+	vec3 color = scene.ambient * material.albedo * ao;
+
+	// Offset the shadow rays so they don't start inside the surface.
+	vec3 shadowOrigin = p + n * marchEpsilon * 2.0;
+
+	for(uint i = 0; i < lightCount(); i++) {
+		vec3 l;
+		vec3 radiance = lightRadiance(getLight(i), p, n, shadowOrigin, l);
+
+		vec3 h = normalize(l + v);
+		float diffuse = max(dot(n, l), 0.0);
+		float specular = pow(max(dot(n, h), 0.0), shininess);
+
+		color += radiance * (diffuseColor * diffuse + specularColor * specular);
+	}
+
+	return color;
+}
+
+float distributionGGX(float NoH, float a) {
+	float a2 = a * a;
+	float d = NoH * NoH * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+// Height-correlated Smith visibility term, G / (4 * NoL * NoV).
+float visibilitySmithGGX(float NoV, float NoL, float a) {
+	float a2 = a * a;
+	float ggxV = NoL * sqrt(NoV * NoV * (1.0 - a2) + a2);
+	float ggxL = NoV * sqrt(NoL * NoL * (1.0 - a2) + a2);
+	return 0.5 / max(ggxV + ggxL, 1e-5);
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 f0) {
+	return f0 + (1.0 - f0) * pow(1.0 - cosTheta, 5.0);
+}
+
+// Analytic fit of the split-sum environment BRDF, see
+// https://www.unrealengine.com/en-US/blog/physically-based-shading-on-mobile
+vec2 envBRDFApprox(float NoV, float roughness) {
+	const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+	const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+	vec4 r = roughness * c0 + c1;
+	float a004 = min(r.x * r.x, exp2(-9.28 * NoV)) * r.x + r.y;
+	return vec2(-1.04, 1.04) * a004 + r.zw;
+}
+
+vec3 shadePBR(vec3 p, vec3 n, vec3 v, Material material, float ao) {
+	float roughness = clamp(material.roughness, 0.04, 1.0);
+	float a = roughness * roughness;
+	vec3 f0 = mix(vec3(0.16 * material.specular * material.specular), material.albedo, material.metallic);
+	vec3 diffuseColor = material.albedo * (1.0 - material.metallic);
+	float NoV = max(dot(n, v), 1e-4);
+
+	vec3 color = vec3(0.0);
+
+	vec3 shadowOrigin = p + n * marchEpsilon * 2.0;
+
+	for(uint i = 0; i < lightCount(); i++) {
+		vec3 l;
+		vec3 radiance = lightRadiance(getLight(i), p, n, shadowOrigin, l);
+
+		vec3 h = normalize(l + v);
+		float NoL = max(dot(n, l), 0.0);
+		float NoH = max(dot(n, h), 0.0);
+		float VoH = max(dot(v, h), 0.0);
+
+		vec3 f = fresnelSchlick(VoH, f0);
+		vec3 specular = distributionGGX(NoH, a) * visibilitySmithGGX(NoV, NoL, a) * f;
+		vec3 diffuse = (1.0 - f) * diffuseColor / PI;
+
+		color += (diffuse + specular) * radiance * NoL;
+	}
+
+	// Image-based ambient lighting from the environment.
+	vec2 envBRDF = envBRDFApprox(NoV, roughness);
+	vec3 specularIBL = sampleEnvironment(reflect(-v, n), roughness) * (f0 * envBRDF.x + envBRDF.y);
+	vec3 kd = 1.0 - fresnelSchlick(NoV, f0);
+	vec3 diffuseIBL = kd * diffuseColor * sampleEnvironment(n, 1.0);
+	color += (diffuseIBL + specularIBL) * ao;
+
+	return color;
+}
+
+vec3 shade(vec3 p, vec3 n, vec3 v, Material material) {
+	float ao = settings.ambientOcclusion != 0 ? calcAO(p, n) : 1.0;
+
+	vec3 color = settings.shadingModel == SHADING_PBR
+		? shadePBR(p, n, v, material, ao)
+		: shadeBlinnPhong(p, n, v, material, ao);
+
+	return material.emissive + color;
+}
+
+// Iterations of the last march, MAX_ITER when it ran out of them.
+int marchSteps;
+// Step sizes of the last march in the step histogram debug view.
+uint stepCounts[STEP_BINS];
+
+// Sphere traces the current ray until it hits a surface. Inside an object the
+// negated distance is marched so the ray stops on the way out.
+//
+// Steps are the distance scaled by the relaxation factor, see Keinert et al.,
+// "Enhanced Sphere Tracing". When the unbounding spheres of two consecutive
+// positions don't overlap the step may have skipped a surface, so the march
+// goes back and carries on with plain steps.
+bool march(bool inside, float relaxation, out uint materialId) {
+	float side = inside ? -1.0 : 1.0;
+	vec3 origin = ray.pos;
+	// Secondary rays carry on the footprint from their origin.
+	float start = distance(origin, frame.cameraPosition);
+	float travelled = marchStart;
+	marchStart = 0.0;
+	float stepLength = 0.0;
+	float previousDist = 0.0;
+	marchEpsilon = MIN_DIST;
+	materialId = 0;
+
+	for(marchSteps = 0; marchSteps < MAX_ITER; marchSteps++) {
+		ray.pos = origin + ray.dir * travelled;
+		vec2 res = sdScene(ray.pos);
+		float dist = side * res.x;
+
+		if(relaxation > 1.0 && abs(dist) + previousDist < stepLength) {
+			travelled += previousDist - stepLength;
+			stepLength = previousDist;
+			relaxation = 1.0;
+			continue;
+		}
+
+		float epsilon = hitEpsilon(start + travelled);
+		if(dist < epsilon) {
+			marchEpsilon = epsilon;
+			materialId = uint(res.y);
+			return true;
+		}
+
+		stepLength = dist * relaxation;
+		previousDist = dist;
+
+		if(PushConstants.debugView == DEBUG_STEP_HISTOGRAM) {
+			float bin = (log2(stepLength) - STEP_LOG_MIN) / STEP_LOG_RANGE * float(STEP_BINS);
+			stepCounts[clamp(int(bin), 0, int(STEP_BINS) - 1)]++;
+		}
+
+		travelled += stepLength;
+		if(travelled > MAX_DIST) {
+			break;
+		}
+	}
+
+	return false;
+}
+
+vec3 background(vec3 dir) {
+	return sampleEnvironment(dir, 0.0);
+}
+
+// Distance the media are marched to along rays that escape the scene.
+const float MEDIA_MAX_DIST = 50.0;
+// Distance and steps of the media's self-shadowing toward each light.
+const float MEDIA_SHADOW_DIST = 8.0;
+const uint MEDIA_SHADOW_STEPS = 4;
+
+float hash(vec3 p) {
+	uvec3 q = uvec3(ivec3(floor(p)) + 32768);
+	return float(pcgHash(q.x + pcgHash(q.y + pcgHash(q.z)))) / 4294967296.0;
+}
+
+float valueNoise(vec3 p) {
+	vec3 i = floor(p);
+	vec3 f = fract(p);
+	f = f * f * (3.0 - 2.0 * f);
+
+	return mix(
+		mix(mix(hash(i), hash(i + vec3(1, 0, 0)), f.x),
+			mix(hash(i + vec3(0, 1, 0)), hash(i + vec3(1, 1, 0)), f.x), f.y),
+		mix(mix(hash(i + vec3(0, 0, 1)), hash(i + vec3(1, 0, 1)), f.x),
+			mix(hash(i + vec3(0, 1, 1)), hash(i + vec3(1, 1, 1)), f.x), f.y),
+		f.z);
+}
+
+// Number of octaves of an fbm of the given base frequency worth evaluating
+// over a footprint of the given radius, those finer than it being dropped.
+uint lodOctaves(uint octaves, float frequency, float radius) {
+	if(settings.footprintLod == 0) {
+		return octaves;
+	}
+	// Octave i has a wavelength of 1 / (frequency * 2.03^i).
+	float visible = log2(1.0 / max(2.0 * radius * frequency, 1e-6)) / log2(2.03);
+	return min(uint(max(visible, 0.0)) + 1, octaves);
+}
+
+float fbm(vec3 p, uint octaves) {
+	float sum = 0.0;
+	float amplitude = 0.5;
+	float total = 0.0;
+	for(uint i = 0; i < octaves; i++) {
+		sum += amplitude * valueNoise(p);
+		total += amplitude;
+		amplitude *= 0.5;
+		p *= 2.03;
+	}
+	return sum / max(total, 1e-6);
+}
+
+float henyeyGreenstein(float cosTheta, float g) {
+	float g2 = g * g;
+	return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * cosTheta, 1.5));
+}
+
+// Entry and exit distances of the ray through a box centered on the origin.
+vec2 rayBox(vec3 origin, vec3 dir, vec3 size) {
+	vec3 inverse = 1.0 / dir;
+	vec3 t0 = (-size - origin) * inverse;
+	vec3 t1 = (size - origin) * inverse;
+	vec3 near = min(t0, t1);
+	vec3 far = max(t0, t1);
+	return vec2(max(max(near.x, near.y), near.z), min(min(far.x, far.y), far.z));
+}
+
+float volumeDensity(Volume volume, vec3 p) {
+	vec3 local = p - volume.position;
+	if(any(greaterThan(abs(local), volume.size))) {
+		return 0.0;
+	}
+
+	if(volume.densityType == VOLUME_NOISE) {
+		float radius = footprint(distance(p, frame.cameraPosition));
+		uint octaves = lodOctaves(volume.noiseOctaves, volume.noiseScale, radius);
+		return volume.density * fbm(p * volume.noiseScale, octaves);
+	} else if(volume.densityType == VOLUME_TEXTURE) {
+		return volume.density * textureLod(volumeTexture, local / (2.0 * volume.size) + 0.5, 0.0).r;
+	}
+	return volume.density;
+}
+
+// Extinction coefficient of the media at p, along with their combined
+// scattering albedo and phase asymmetry.
+float sampleMedia(vec3 p, out vec3 albedo, out float anisotropy) {
+	float fog = scene.fogDensity * exp(-scene.fogFalloff * max(p.y - scene.fogHeight, 0.0));
+	float extinction = fog;
+	albedo = scene.fogAlbedo * fog;
+	anisotropy = scene.fogAnisotropy * fog;
+
+	for(uint i = 0; i < scene.volumeCount; i++) {
+		float density = volumeDensity(volumes[i], p);
+		extinction += density;
+		albedo += volumes[i].albedo * density;
+		anisotropy += volumes[i].anisotropy * density;
+	}
+
+	if(extinction > 0.0) {
+		albedo /= extinction;
+		anisotropy /= extinction;
+	}
+	return extinction;
+}
+
+// Transmittance of the media along a short segment.
+float mediaTransmittance(vec3 origin, vec3 dir, float dist) {
+	float stepSize = dist / float(MEDIA_SHADOW_STEPS);
+	float opticalDepth = 0.0;
+	for(uint i = 0; i < MEDIA_SHADOW_STEPS; i++) {
+		vec3 albedo;
+		float anisotropy;
+		opticalDepth += sampleMedia(origin + dir * stepSize * (float(i) + 0.5), albedo, anisotropy) * stepSize;
+	}
+	return exp(-opticalDepth);
+}
+
+// Marches the fog and volumes between the origin and the surface hit at dist,
+// with single scattering from every light shadowed by the scene. Returns the
+// in-scattered radiance and the transmittance to the surface.
+vec3 marchMedia(vec3 origin, vec3 dir, float dist, out vec3 transmittance) {
+	transmittance = vec3(1.0);
+	if(settings.volumetrics == 0 || (scene.fogDensity <= 0.0 && scene.volumeCount == 0)) {
+		return vec3(0.0);
+	}
+
+	// Without fog only the parts of the ray inside volumes are marched.
+	vec2 range = vec2(0.0, min(dist, MEDIA_MAX_DIST));
+	if(scene.fogDensity <= 0.0) {
+		vec2 bounds = vec2(range.y, 0.0);
+		for(uint i = 0; i < scene.volumeCount; i++) {
+			vec2 t = rayBox(origin - volumes[i].position, dir, volumes[i].size);
+			if(t.x <= t.y && t.y > 0.0) {
+				bounds = vec2(min(bounds.x, max(t.x, 0.0)), max(bounds.y, t.y));
+			}
+		}
+		range = vec2(bounds.x, min(bounds.y, range.y));
+		if(range.x >= range.y) {
+			return vec3(0.0);
+		}
+	}
+
+	float stepSize = (range.y - range.x) / float(settings.volumeSteps);
+	float t = range.x + stepSize * random();
+	vec3 ambient = sampleEnvironment(vec3(0.0, 1.0, 0.0), 1.0);
+	vec3 inscattered = vec3(0.0);
+
+	for(uint i = 0; i < settings.volumeSteps; i++, t += stepSize) {
+		vec3 p = origin + dir * t;
+		vec3 albedo;
+		float anisotropy;
+		float extinction = sampleMedia(p, albedo, anisotropy);
+		if(extinction <= 0.0) {
+			continue;
+		}
+
+		vec3 lighting = ambient;
+		for(uint j = 0; j < lightCount(); j++) {
+			vec3 l;
+			float lightDist;
+			vec3 radiance = lightIncident(getLight(j), p, l, lightDist);
+			if(settings.shadows != 0) {
+				radiance *= calcSoftShadow(p, l, lightDist);
+			}
+			radiance *= mediaTransmittance(p, l, min(lightDist, MEDIA_SHADOW_DIST));
+			lighting += radiance * henyeyGreenstein(dot(dir, l), anisotropy);
+		}
+
+		// Integrates the in-scattering analytically over the step, see
+		// Hillaire, "Physically Based and Unified Volumetric Rendering in
+		// Frostbite".
+		float stepTransmittance = exp(-extinction * stepSize);
+		inscattered += transmittance * albedo * lighting * (1.0 - stepTransmittance);
+		transmittance *= stepTransmittance;
+
+		if(max(transmittance.r, max(transmittance.g, transmittance.b)) < 0.01) {
+			transmittance = vec3(0.0);
+			break;
+		}
+	}
+
+	return inscattered;
+}
+
+// Distance the clouds fade out at, in multiples of the layer's top altitude.
+const float CLOUD_HORIZON_DIST = 40.0;
+// Length of the ray marched through the layer, in multiples of its
+// thickness, cutting short the long rays near the horizon.
+const float CLOUD_MAX_SPAN = 4.0;
+// Weight of the backward lobe of the clouds' phase function.
+const float CLOUD_BACK_SCATTERING_WEIGHT = 0.3;
+
+float remap(float value, float low, float high, float newLow, float newHigh) {
+	return newLow + (value - low) * (newHigh - newLow) / (high - low);
+}
+
+// Cells per tile of the Worley noise dominating the detail erosion, see
+// clouds.rs.
+const float CLOUD_DETAIL_CELLS = 4.0;
+
+// Cloud extinction coefficient at p, see Schneider, "The Real-time
+// Volumetric Cloudscapes of Horizon Zero Dawn".
+float cloudDensity(vec3 p) {
+	float height = (p.y - scene.cloudBottom) / (scene.cloudTop - scene.cloudBottom);
+	if(height <= 0.0 || height >= 1.0) {
+		return 0.0;
+	}
+
+	vec3 q = (p - scene.cloudWind * frame.time) * scene.cloudScale;
+	vec4 noise = textureLod(cloudNoise, q, 0.0);
+	float worley = dot(noise.gba, vec3(0.625, 0.25, 0.125));
+	float shape = remap(noise.r, worley - 1.0, 1.0, 0.0, 1.0);
+
+	// Rounded bottoms and tops, then only the densest parts are kept as
+	// coverage decreases.
+	shape *= smoothstep(0.0, 0.1, height) * (1.0 - smoothstep(0.6, 1.0, height));
+	shape = clamp(remap(shape, 1.0 - scene.cloudCoverage, 1.0, 0.0, 1.0), 0.0, 1.0) * scene.cloudCoverage;
+	if(shape <= 0.0) {
+		return 0.0;
+	}
+
+	// High frequency erosion of the edges, wispy at the bottom and billowy
+	// toward the top. It fades out as its cells shrink below a pixel.
+	float erosion = 0.35;
+	if(settings.footprintLod != 0) {
+		float cellSize = 1.0 / (scene.cloudScale * scene.cloudDetailScale * CLOUD_DETAIL_CELLS);
+		float radius = footprint(distance(p, frame.cameraPosition));
+		erosion *= 1.0 - smoothstep(0.5, 2.0, 2.0 * radius / cellSize);
+	}
+	if(erosion > 0.0) {
+		vec4 detailNoise = textureLod(cloudNoise, q * scene.cloudDetailScale, 0.0);
+		float detail = dot(detailNoise.gba, vec3(0.625, 0.25, 0.125));
+		detail = mix(1.0 - detail, detail, clamp(height * 5.0, 0.0, 1.0));
+		shape = clamp(remap(shape, detail * erosion, 1.0, 0.0, 1.0), 0.0, 1.0);
+	}
+
+	return shape * scene.cloudDensity;
+}
+
+// Optical depth of the clouds from p toward a directional light.
+float cloudLightDepth(vec3 p, vec3 l) {
+	float thickness = scene.cloudTop - scene.cloudBottom;
+	float span = min((scene.cloudTop - p.y) / max(l.y, 1e-3), thickness * CLOUD_MAX_SPAN);
+	float stepSize = span / float(settings.cloudLightSteps);
+
+	float opticalDepth = 0.0;
+	for(uint i = 0; i < settings.cloudLightSteps; i++) {
+		opticalDepth += cloudDensity(p + l * stepSize * (float(i) + 0.5)) * stepSize;
+	}
+	return opticalDepth;
+}
+
+// Marches the cloud layer along a ray escaping the scene, lit by the
+// directional lights and the sky. Returns the in-scattered radiance and the
+// transmittance to the background.
+vec3 marchClouds(vec3 origin, vec3 dir, out vec3 transmittance) {
+	transmittance = vec3(1.0);
+	if(settings.clouds == 0 || scene.cloudDensity <= 0.0 || abs(dir.y) < 1e-4) {
+		return vec3(0.0);
+	}
+
+	float thickness = scene.cloudTop - scene.cloudBottom;
+	float horizonDist = CLOUD_HORIZON_DIST * scene.cloudTop;
+	vec2 slab = (vec2(scene.cloudBottom, scene.cloudTop) - origin.y) / dir.y;
+	vec2 range = vec2(max(min(slab.x, slab.y), 0.0), max(slab.x, slab.y));
+	range.y = min(range.y, range.x + thickness * CLOUD_MAX_SPAN);
+	if(range.x >= range.y || range.x >= horizonDist) {
+		return vec3(0.0);
+	}
+
+	float stepSize = (range.y - range.x) / float(settings.cloudSteps);
+	float t = range.x + stepSize * random();
+	vec3 ambient = sampleEnvironment(vec3(0.0, 1.0, 0.0), 1.0);
+	vec3 inscattered = vec3(0.0);
+
+	for(uint i = 0; i < settings.cloudSteps; i++, t += stepSize) {
+		vec3 p = origin + dir * t;
+		float extinction = cloudDensity(p);
+		if(extinction <= 0.0) {
+			continue;
+		}
+
+		// The sky lights the tops of the clouds more than their bottoms.
+		float height = (p.y - scene.cloudBottom) / thickness;
+		vec3 lighting = ambient * mix(0.5, 1.0, height);
+		for(uint j = 0; j < lightCount(); j++) {
+			Light light = getLight(j);
+			if(light.kind != LIGHT_DIRECTIONAL) {
+				continue;
+			}
+
+			vec3 l;
+			float lightDist;
+			vec3 radiance = lightIncident(light, p, l, lightDist);
+			if(l.y <= 0.0) {
+				continue;
+			}
+
+			// Beer's law with a second, weaker extinction standing in for
+			// multiple scattering, which keeps the shadowed side from going
+			// black.
+			float depth = cloudLightDepth(p, l);
+			float lightTransmittance = max(exp(-depth), 0.7 * exp(-0.25 * depth));
+
+			// Dual-lobe phase: the forward lobe gives the silver lining of
+			// clouds in front of the sun.
+			float cosTheta = dot(dir, l);
+			float phase = mix(
+				henyeyGreenstein(cosTheta, scene.cloudForwardScattering),
+				henyeyGreenstein(cosTheta, -scene.cloudBackScattering),
+				CLOUD_BACK_SCATTERING_WEIGHT);
+
+			lighting += radiance * lightTransmittance * phase;
+		}
+
+		float stepTransmittance = exp(-extinction * stepSize);
+		inscattered += transmittance * lighting * (1.0 - stepTransmittance);
+		transmittance *= stepTransmittance;
+
+		if(transmittance.r < 0.01) {
+			transmittance = vec3(0.0);
+			break;
+		}
+	}
+
+	// Distant clouds fade into the sky instead of aliasing at the horizon.
+	float fade = 1.0 - smoothstep(0.25, 1.0, range.x / horizonDist);
+	transmittance = mix(vec3(1.0), transmittance, fade);
+	return inscattered * fade;
+}
+
+// Guide buffers of the denoiser, filled from the primary ray's hit.
+vec4 firstHitNormalDepth = vec4(0.0, 0.0, 0.0, MAX_DIST);
+vec3 firstHitAlbedo = vec3(0.0);
+
+void recordFirstHit(vec3 p, vec3 n, Material material) {
+	firstHitNormalDepth = vec4(n, distance(frame.cameraPosition, p));
+	firstHitAlbedo = material.albedo;
+}
+
+vec3 traceDirect() {
+	vec3 color = vec3(0.0);
+	vec3 throughput = vec3(1.0);
+	bool inside = false;
+
+	for(uint bounce = 0; bounce <= settings.maxBounces; bounce++) {
+		vec3 origin = ray.pos;
+		uint materialId;
+		bool hit = march(inside, settings.relaxation, materialId);
+
+		if(!inside) {
+			vec3 transmittance;
+			color += throughput * marchMedia(origin, ray.dir, hit ? distance(origin, ray.pos) : MAX_DIST, transmittance);
+			throughput *= transmittance;
+		}
+
+		if(!hit) {
+			vec3 cloudTransmittance;
+			color += throughput * marchClouds(origin, ray.dir, cloudTransmittance);
+			throughput *= cloudTransmittance;
+			color += throughput * background(ray.dir);
+			if(bounce == 0) {
+				color += throughput * sunDisk(ray.dir);
+			}
+			break;
+		}
+
+		Material material = materials[materialId];
+		vec3 p = ray.pos;
+		// Normals always face the incoming ray, including inside objects.
+		vec3 n = inside ? -calcNormal(p) : calcNormal(p);
+		vec3 v = -ray.dir;
+
+		if(bounce == 0) {
+			recordFirstHit(p, n, material);
+		}
+
+		float reflectivity = clamp(material.reflectivity, 0.0, 1.0);
+		float transmission = clamp(material.transmission, 0.0, 1.0 - reflectivity);
+		float surface = 1.0 - reflectivity - transmission;
+
+		if(!inside && surface > 0.0) {
+			color += throughput * surface * shade(p, n, v, material);
+		}
+
+		if(bounce == settings.maxBounces) {
+			break;
+		}
+
+		vec3 reflected = reflect(ray.dir, n);
+
+		if(transmission > 0.0) {
+			float eta = inside ? material.ior : 1.0 / material.ior;
+			float r0 = (1.0 - material.ior) / (1.0 + material.ior);
+			float f0 = r0 * r0;
+			float fresnel = fresnelSchlick(max(dot(n, v), 0.0), vec3(f0)).x;
+			vec3 refracted = refract(ray.dir, n, eta);
+
+			if(refracted == vec3(0.0)) {
+				// Total internal reflection, the ray stays on the same side.
+				ray.dir = reflected;
+				ray.pos = p + n * marchEpsilon * 2.0;
+				throughput *= transmission;
+			} else {
+				// Only the refracted path is followed, the reflected part
+				// falls back to the background.
+				color += throughput * transmission * fresnel * background(reflected);
+				ray.dir = refracted;
+				ray.pos = p - n * marchEpsilon * 2.0;
+				throughput *= transmission * (1.0 - fresnel) * (inside ? vec3(1.0) : material.albedo);
+				inside = !inside;
+			}
+		} else if(reflectivity > 0.0) {
+			ray.dir = reflected;
+			ray.pos = p + n * marchEpsilon * 2.0;
+			throughput *= reflectivity * mix(vec3(1.0), material.albedo, material.metallic);
+		} else {
+			break;
+		}
+	}
+
+	return color;
+}
+
+vec3 sampleCosineHemisphere(vec3 n) {
+	float phi = 2.0 * PI * random();
+	float r2 = random();
+	float r = sqrt(r2);
+
+	vec3 tangent = normalize(cross(abs(n.x) > 0.5 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0), n));
+	vec3 bitangent = cross(n, tangent);
+
+	return tangent * (r * cos(phi)) + bitangent * (r * sin(phi)) + n * sqrt(1.0 - r2);
+}
+
+vec3 randomUnitVector() {
+	float z = random() * 2.0 - 1.0;
+	float phi = 2.0 * PI * random();
+	float r = sqrt(1.0 - z * z);
+	return vec3(r * cos(phi), r * sin(phi), z);
+}
+
+float luminance(vec3 color) {
+	return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Monte Carlo estimate of the radiance along the current ray. Scene lights are
+// sampled explicitly at every opaque hit, the next direction is picked by
+// importance sampling the material.
+vec3 tracePath() {
+	vec3 radiance = vec3(0.0);
+	vec3 throughput = vec3(1.0);
+	bool inside = false;
+
+	for(uint bounce = 0; bounce <= settings.maxBounces; bounce++) {
+		vec3 origin = ray.pos;
+		uint materialId;
+		bool hit = march(inside, settings.relaxation, materialId);
+
+		if(!inside) {
+			vec3 transmittance;
+			radiance += throughput * marchMedia(origin, ray.dir, hit ? distance(origin, ray.pos) : MAX_DIST, transmittance);
+			throughput *= transmittance;
+		}
+
+		if(!hit) {
+			vec3 cloudTransmittance;
+			radiance += throughput * marchClouds(origin, ray.dir, cloudTransmittance);
+			throughput *= cloudTransmittance;
+			radiance += throughput * background(ray.dir);
+			if(bounce == 0) {
+				radiance += throughput * sunDisk(ray.dir);
+			}
+			break;
+		}
+
+		Material material = materials[materialId];
+		vec3 p = ray.pos;
+		vec3 n = inside ? -calcNormal(p) : calcNormal(p);
+		vec3 v = -ray.dir;
+
+		if(bounce == 0) {
+			recordFirstHit(p, n, material);
+		}
+
+		if(!inside) {
+			radiance += throughput * material.emissive;
+		}
+
+		float reflectivity = clamp(material.reflectivity, 0.0, 1.0);
+		float transmission = clamp(material.transmission, 0.0, 1.0 - reflectivity);
+		float choice = random();
+
+		if(choice < transmission) {
+			float eta = inside ? material.ior : 1.0 / material.ior;
+			float r0 = (1.0 - material.ior) / (1.0 + material.ior);
+			float fresnel = fresnelSchlick(max(dot(n, v), 0.0), vec3(r0 * r0)).x;
+			vec3 refracted = refract(ray.dir, n, eta);
+
+			if(refracted == vec3(0.0) || random() < fresnel) {
+				ray.dir = reflect(ray.dir, n);
+				ray.pos = p + n * marchEpsilon * 2.0;
+			} else {
+				ray.dir = refracted;
+				ray.pos = p - n * marchEpsilon * 2.0;
+				throughput *= inside ? vec3(1.0) : material.albedo;
+				inside = !inside;
+			}
+		} else if(choice < transmission + reflectivity) {
+			ray.dir = normalize(reflect(ray.dir, n) + material.roughness * material.roughness * randomUnitVector());
+			ray.pos = p + n * marchEpsilon * 2.0;
+			throughput *= mix(vec3(1.0), material.albedo, material.metallic);
+			if(dot(ray.dir, n) <= 0.0) {
+				break;
+			}
+		} else {
+			float roughness = clamp(material.roughness, 0.04, 1.0);
+			float a = roughness * roughness;
+			vec3 f0 = mix(vec3(0.16 * material.specular * material.specular), material.albedo, material.metallic);
+			vec3 diffuseColor = material.albedo * (1.0 - material.metallic);
+			vec3 shadowOrigin = p + n * marchEpsilon * 2.0;
+			float NoV = max(dot(n, v), 1e-4);
+
+			for(uint i = 0; i < lightCount(); i++) {
+				vec3 l;
+				vec3 lightColor = lightRadiance(getLight(i), p, n, shadowOrigin, l);
+
+				vec3 h = normalize(l + v);
+				float NoL = max(dot(n, l), 0.0);
+				vec3 f = fresnelSchlick(max(dot(v, h), 0.0), f0);
+				vec3 specular = distributionGGX(max(dot(n, h), 0.0), a) * visibilitySmithGGX(NoV, NoL, a) * f;
+				vec3 diffuse = (1.0 - f) * diffuseColor / PI;
+
+				radiance += throughput * (diffuse + specular) * lightColor * NoL;
+			}
+
+			// Pick between the specular and diffuse lobes proportionally
+			// to their expected contribution.
+			vec3 f = fresnelSchlick(NoV, f0);
+			float specularWeight = luminance(f);
+			float diffuseWeight = luminance((1.0 - f) * diffuseColor);
+			float specularProbability = specularWeight / max(specularWeight + diffuseWeight, 1e-4);
+
+			if(random() < specularProbability) {
+				ray.dir = normalize(reflect(ray.dir, n) + a * randomUnitVector());
+				throughput *= f / specularProbability;
+			} else {
+				ray.dir = sampleCosineHemisphere(n);
+				throughput *= (1.0 - f) * diffuseColor / (1.0 - specularProbability);
+			}
+
+			ray.pos = shadowOrigin;
+			if(dot(ray.dir, n) <= 0.0) {
+				break;
+			}
+		}
+
+		// Russian roulette once the path had a few bounces.
+		if(bounce >= 3) {
+			float survival = clamp(max(throughput.r, max(throughput.g, throughput.b)), 0.05, 1.0);
+			if(random() > survival) {
+				break;
+			}
+			throughput /= survival;
+		}
+	}
+
+	return radiance;
+}
+
+// Blue to red through cyan, green and yellow.
+vec3 heatmap(float t) {
+	t = clamp(t, 0.0, 1.0);
+	return clamp(vec3(4.0 * t - 2.0, 2.0 - abs(4.0 * t - 2.0), 2.0 - 4.0 * t), 0.0, 1.0);
+}
+
+// Marches the primary ray alone and visualizes it for the debug view. Rays
+// that ran out of iterations without hitting anything are magenta.
+vec3 traceDebug() {
+	vec3 origin = ray.pos;
+	float start = marchStart;
+	uint materialId;
+	for(uint bin = 0; bin < STEP_BINS; bin++) {
+		stepCounts[bin] = 0;
+	}
+
+	// The same ray sphere traced with plain steps, to compare with.
+	int plainSteps = 0;
+	if(PushConstants.debugView == DEBUG_ITERATIONS && settings.relaxation > 1.0) {
+		march(false, 1.0, materialId);
+		plainSteps = marchSteps;
+		ray.pos = origin;
+		marchStart = start;
+	}
+
+	bool hit = march(false, settings.relaxation, materialId);
+
+	if(PushConstants.debugView == DEBUG_ITERATIONS) {
+		atomicAdd(rayCount, 1);
+		atomicAdd(iterations, uint(marchSteps));
+		atomicAdd(plainIterations, uint(settings.relaxation > 1.0 ? plainSteps : marchSteps));
+	}
+
+	if(PushConstants.debugView == DEBUG_STEP_HISTOGRAM) {
+		for(uint bin = 0; bin < STEP_BINS; bin++) {
+			if(stepCounts[bin] > 0) {
+				atomicAdd(stepBins[bin], stepCounts[bin]);
+			}
+		}
+	}
+
+	if(!hit && marchSteps == MAX_ITER) {
+		return vec3(1.0, 0.0, 1.0);
+	}
+	if(PushConstants.debugView == DEBUG_ITERATIONS
+		|| PushConstants.debugView == DEBUG_STEP_HISTOGRAM) {
+		return heatmap(float(marchSteps) / float(MAX_ITER));
+	}
+	if(!hit) {
+		return vec3(0.0);
+	}
+
+	vec3 p = ray.pos;
+	vec3 n = calcNormal(p);
+	recordFirstHit(p, n, materials[materialId]);
+
+	if(PushConstants.debugView == DEBUG_DEPTH) {
+		return vec3(1.0 - distance(origin, p) / MAX_DIST);
+	} else if(PushConstants.debugView == DEBUG_NORMALS) {
+		return n * 0.5 + 0.5;
+	}
+	// DEBUG_MATERIALS, a random color per material.
+	uint hash = pcgHash(materialId);
+	return vec3(hash & 0xffu, (hash >> 8) & 0xffu, (hash >> 16) & 0xffu) / 255.0;
+}
+
+// Radius of the supersampling's reconstruction filter, in pixels.
+float filterRadius() {
+	if(settings.ssaaFilter == FILTER_GAUSSIAN) {
+		return 1.5;
+	} else if(settings.ssaaFilter == FILTER_MITCHELL) {
+		return 2.0;
+	}
+	return 0.5;
+}
+
+// Mitchell-Netravali filter with B = C = 1/3, over a radius of 2.
+float mitchell(float x) {
+	const float B = 1.0 / 3.0;
+	const float C = 1.0 / 3.0;
+	x = abs(x);
+	if(x < 1.0) {
+		return ((12.0 - 9.0 * B - 6.0 * C) * x * x * x
+			+ (-18.0 + 12.0 * B + 6.0 * C) * x * x
+			+ (6.0 - 2.0 * B)) / 6.0;
+	} else if(x < 2.0) {
+		return ((-B - 6.0 * C) * x * x * x
+			+ (6.0 * B + 30.0 * C) * x * x
+			+ (-12.0 * B - 48.0 * C) * x
+			+ (8.0 * B + 24.0 * C)) / 6.0;
+	}
+	return 0.0;
+}
+
+// Weight of a supersample at the given offset from the pixel's center, in
+// pixels. Mitchell's negative lobes sharpen the image.
+float filterWeight(vec2 offset) {
+	if(settings.ssaaFilter == FILTER_GAUSSIAN) {
+		// Shifted down to reach zero at the radius.
+		const float SIGMA = 0.5;
+		float radius = filterRadius();
+		vec2 gaussian = exp(-offset * offset / (2.0 * SIGMA * SIGMA))
+			- exp(-radius * radius / (2.0 * SIGMA * SIGMA));
+		return max(gaussian.x, 0.0) * max(gaussian.y, 0.0);
+	} else if(settings.ssaaFilter == FILTER_MITCHELL) {
+		return mitchell(offset.x) * mitchell(offset.y);
+	}
+	return 1.0;
+}
+
+// Position of the supersample in the cell (i, j) of the n×n grid, within the
+// unit square.
+vec2 supersamplePosition(uint i, uint j, uint n) {
+	if(settings.ssaaPattern == SAMPLE_STRATIFIED) {
+		return (vec2(i, j) + vec2(random(), random())) / float(n);
+	} else if(settings.ssaaPattern == SAMPLE_ROTATED_GRID) {
+		// Each cell is offset by its row and column, so that every sample
+		// has its own row and column of the finer n²×n² grid.
+		return vec2(
+			float(i) + (float(j) + 0.5) / float(n),
+			float(j) + (float(n - 1 - i) + 0.5) / float(n)) / float(n);
+	}
+	return (vec2(i, j) + 0.5) / float(n);
+}
+
+// Direct rendering of the pixel supersampled over the footprint of the
+// reconstruction filter. The first hit guides come from the last sample
+// hitting a surface. The TAA jitter isn't applied, so that the samples stay
+// on their pattern every frame.
+vec3 traceSupersampled() {
+	uint n = settings.ssaaGridSize;
+	float radius = filterRadius();
+	vec3 sum = vec3(0.0);
+	float weightSum = 0.0;
+
+	for(uint j = 0; j < n; j++) {
+		for(uint i = 0; i < n; i++) {
+			vec2 offset = (supersamplePosition(i, j, n) - 0.5) * 2.0 * radius;
+			generateRay(vec2(0.5) + offset);
+			// Samples can leave the pixel's cone march cell, so they march
+			// from the camera.
+			marchStart = 0.0;
+
+			float weight = filterWeight(offset);
+			sum += traceDirect() * weight;
+			weightSum += weight;
+		}
+	}
+
+	return max(sum / max(weightSum, 1e-4), 0.0);
+}
+
+// Distance the primary ray of the pixel can start marching at.
+float coneStart() {
+	if(settings.conePrepass == 0) {
+		return 0.0;
+	}
+	return texelFetch(coneStarts, ivec2(fragCoord) / CONE_CELL_SIZE, 0).r;
+}
+
+// Counts the iterations of the current primary ray into MarchStats, with the
+// over-relaxation and with plain steps, leaving the ray as it was.
+void countPrimaryIterations() {
+	vec3 origin = ray.pos;
+	float start = marchStart;
+	uint materialId;
+
+	march(false, settings.relaxation, materialId);
+	int steps = marchSteps;
+	int plainSteps = marchSteps;
+	if(settings.relaxation > 1.0) {
+		ray.pos = origin;
+		marchStart = start;
+		march(false, 1.0, materialId);
+		plainSteps = marchSteps;
+	}
+
+	atomicAdd(rayCount, 1);
+	atomicAdd(iterations, uint(steps));
+	atomicAdd(plainIterations, uint(plainSteps));
+	ray.pos = origin;
+	marchStart = start;
+}
+
+// Renders the pixel at fragCoord, returning its color and the denoiser's
+// guides.
+void render(out vec4 color, out vec4 normalDepth, out vec4 albedo) {
+	initRandom();
+	marchStart = coneStart();
+
+	if(settings.marchStats != 0 && PushConstants.debugView == DEBUG_OFF) {
+		generateRay(vec2(0.5));
+		countPrimaryIterations();
+	}
+
+	if(PushConstants.debugView != DEBUG_OFF) {
+		generateRay(vec2(0.5));
+		color = vec4(traceDebug(), 1.0);
+	} else if(settings.renderMode == RENDER_PATH_TRACED) {
+		generateRay(vec2(random(), random()));
+		vec3 sampleColor = tracePath();
+
+		ivec2 pixel = ivec2(fragCoord);
+		vec3 sum = sampleColor;
+		if(frame.sampleCount > 0) {
+			sum += imageLoad(accumulation, pixel).rgb;
+		}
+		imageStore(accumulation, pixel, vec4(sum, 1.0));
+
+		color = vec4(sum / float(frame.sampleCount + 1), 1.0);
+	} else if(settings.ssaaGridSize > 1) {
+		color = vec4(traceSupersampled(), 1.0);
+	} else {
+		generateRay(vec2(0.5) + frame.jitter);
+		color = vec4(traceDirect(), 1.0);
+	}
+
+	normalDepth = firstHitNormalDepth;
+	albedo = vec4(firstHitAlbedo, 1.0);
+}
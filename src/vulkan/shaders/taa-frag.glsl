@@ -0,0 +1,92 @@
+#version 460
+
+// Temporal anti-aliasing resolve: blends the jittered ray marcher's output
+// with the history of the previous frames, reprojected with the previous
+// camera and the depth of the first hit. The history is clamped to the colors
+// around the pixel to reject what moved or got disoccluded.
+
+layout(origin_upper_left) in vec4 gl_FragCoord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform constants {
+	float fov; // Horizontal field of view of the ray marcher, in degrees.
+} PushConstants;
+
+layout(set = 0, binding = 0) uniform sampler2D colorImage;
+layout(set = 0, binding = 1) uniform sampler2D normalDepthImage;
+// Resolved image of the previous frame, sampled bilinearly.
+layout(set = 0, binding = 2) uniform sampler2D historyImage;
+
+layout(set = 0, binding = 3) uniform TaaData {
+	vec3 cameraPosition;
+	uint historyValid; // 0 when the history was just created.
+	vec3 cameraRight;
+	float padding0;
+	vec3 cameraUp;
+	float padding1;
+	vec3 cameraForward;
+	float padding2;
+	vec3 previousCameraPosition;
+	float padding3;
+	vec3 previousCameraRight;
+	float padding4;
+	vec3 previousCameraUp;
+	float padding5;
+	vec3 previousCameraForward;
+	float padding6;
+} taa;
+
+// Weight of the current frame in the blend, the rest being the history.
+const float CURRENT_WEIGHT = 0.1;
+
+// Half extents of the image plane at a distance of 1 from the camera, like
+// generateRay in ray-marcher.glsl.
+vec2 halfExtents(vec2 size) {
+	float halfWidth = tan(radians(PushConstants.fov / 2.0));
+	return vec2(halfWidth, halfWidth * size.y / size.x);
+}
+
+void main() {
+	ivec2 pixel = ivec2(gl_FragCoord.xy);
+	ivec2 size = textureSize(colorImage, 0);
+	vec3 color = texelFetch(colorImage, pixel, 0).rgb;
+
+	if(taa.historyValid == 0) {
+		f_color = vec4(color, 1.0);
+		return;
+	}
+
+	// Colors around the pixel bound the history.
+	vec3 minColor = color;
+	vec3 maxColor = color;
+	for(int y = -1; y <= 1; y++) {
+		for(int x = -1; x <= 1; x++) {
+			vec3 neighbor = texelFetch(colorImage, clamp(pixel + ivec2(x, y), ivec2(0), size - 1), 0).rgb;
+			minColor = min(minColor, neighbor);
+			maxColor = max(maxColor, neighbor);
+		}
+	}
+
+	// World position of the first hit through the pixel's center.
+	vec2 extents = halfExtents(vec2(size));
+	vec2 offset = vec2(1.0, -1.0) * ((gl_FragCoord.xy / vec2(size)) * 2.0 - 1.0) * extents;
+	vec3 direction = normalize(offset.x * taa.cameraRight + offset.y * taa.cameraUp + taa.cameraForward);
+	vec3 position = taa.cameraPosition + direction * texelFetch(normalDepthImage, pixel, 0).w;
+
+	// Where it was seen in the previous frame.
+	vec3 toPosition = position - taa.previousCameraPosition;
+	float depth = dot(toPosition, taa.previousCameraForward);
+	vec2 previousOffset = vec2(
+		dot(toPosition, taa.previousCameraRight),
+		dot(toPosition, taa.previousCameraUp)) / depth;
+	vec2 uv = (vec2(1.0, -1.0) * previousOffset / extents + 1.0) * 0.5;
+
+	if(depth <= 0.0 || any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+		f_color = vec4(color, 1.0);
+		return;
+	}
+
+	vec3 history = clamp(texture(historyImage, uv).rgb, minColor, maxColor);
+	f_color = vec4(mix(history, color, CURRENT_WEIGHT), 1.0);
+}
@@ -0,0 +1,264 @@
+#version 460
+
+// Upscales the rendered HDR image to the swapchain if it was rendered at a
+// lower resolution, exposes and tone maps it, grades it through the LUT, then
+// encodes it for the swapchain.
+
+layout(origin_upper_left) in vec4 gl_FragCoord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform constants {
+	vec2 outputSize; // Size of the swapchain image, in pixels.
+	uint debugView;
+} PushConstants;
+
+layout(set = 0, binding = 0) uniform sampler2D colorImage;
+
+// Adapted scene luminance, written by exposure-comp.glsl.
+layout(std430, set = 0, binding = 1) readonly buffer Exposure {
+	float averageLuminance;
+};
+
+layout(set = 0, binding = 2) uniform ToneMapSettings {
+	uint toneMapper;
+	uint autoExposure;
+	float exposure; // In stops, a compensation when autoExposure is set.
+	float adaptation; // Fraction of the way to the new luminance this frame.
+	vec3 lutDomainMin; // Input colors of the LUT's first and last entries.
+	float lutStrength; // 0 without color grading.
+	vec3 lutDomainMax;
+	uint outputEncoding;
+	uint upscaleFilter;
+} settings;
+
+// Color grading LUT, applied to sRGB encoded colors.
+layout(set = 0, binding = 3) uniform sampler3D lut;
+
+// Must match ray-marcher.glsl.
+const uint STEP_BINS = 32;
+
+// Step sizes of the primary rays, written in the step histogram debug view.
+layout(std430, set = 0, binding = 4) readonly buffer StepHistogram {
+	uint stepBins[STEP_BINS];
+};
+
+// Debug views show the ray marcher's output as is. Must match
+// ray-marcher.glsl.
+const uint DEBUG_OFF = 0;
+const uint DEBUG_STEP_HISTOGRAM = 5;
+
+// Size and margin of the step histogram overlay, in pixels.
+const vec2 OVERLAY_SIZE = vec2(256.0, 128.0);
+const float OVERLAY_MARGIN = 16.0;
+
+const uint TONE_MAPPER_REINHARD = 0;
+const uint TONE_MAPPER_ACES = 1;
+const uint TONE_MAPPER_AGX = 2;
+const uint TONE_MAPPER_NONE = 3;
+
+const uint ENCODING_LINEAR = 0;
+const uint ENCODING_SRGB = 1;
+
+const uint UPSCALE_BILINEAR = 0;
+const uint UPSCALE_EDGE_AWARE = 1;
+
+// How fast the edge-aware upscaling weights fall off with the difference of
+// compressed luminance.
+const float EDGE_SHARPNESS = 16.0;
+
+// Middle gray the auto exposure maps the average luminance to.
+const float EXPOSURE_KEY = 0.18;
+
+float luminance(vec3 color) {
+	return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Reinhard on the luminance, which keeps the hue of bright colors.
+vec3 reinhard(vec3 color) {
+	return color / (1.0 + luminance(color));
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms,
+// from sRGB to sRGB through the ACES color space.
+vec3 aces(vec3 color) {
+	const mat3 inputMatrix = mat3(
+		0.59719, 0.07600, 0.02840,
+		0.35458, 0.90834, 0.13383,
+		0.04823, 0.01566, 0.83777);
+	const mat3 outputMatrix = mat3(
+		1.60475, -0.10208, -0.00327,
+		-0.53108, 1.10813, -0.07276,
+		-0.07367, -0.00605, 1.07602);
+
+	color = inputMatrix * color;
+	vec3 a = color * (color + 0.0245786) - 0.000090537;
+	vec3 b = color * (0.983729 * color + 0.4329510) + 0.238081;
+	return outputMatrix * (a / b);
+}
+
+// Polynomial fit of AgX's default sigmoid contrast curve.
+vec3 agxContrast(vec3 x) {
+	vec3 x2 = x * x;
+	vec3 x4 = x2 * x2;
+	return 15.5 * x4 * x2
+		- 40.14 * x4 * x
+		+ 31.96 * x4
+		- 6.868 * x2 * x
+		+ 0.4298 * x2
+		+ 0.1191 * x
+		- 0.00232;
+}
+
+// Minimal AgX, see Benjamin Wrensch, "Minimal AgX Implementation".
+vec3 agx(vec3 color) {
+	const mat3 inset = mat3(
+		0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+		0.0784335999999992, 0.878468636469772, 0.0784336,
+		0.0792237451477643, 0.0791661274605434, 0.879142973793104);
+	const mat3 outset = mat3(
+		1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+		-0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+		-0.0990297440797205, -0.0989611768448433, 1.15107367264116);
+	const float minEv = -12.47393;
+	const float maxEv = 4.026069;
+
+	color = clamp(log2(max(inset * color, 1e-10)), minEv, maxEv);
+	color = agxContrast((color - minEv) / (maxEv - minEv));
+	// The curve outputs values for a 2.2 gamma display, linearized again
+	// for the output encoding.
+	return pow(max(outset * color, 0.0), vec3(2.2));
+}
+
+vec3 linearToSrgb(vec3 color) {
+	return mix(
+		color * 12.92,
+		1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055,
+		greaterThan(color, vec3(0.0031308)));
+}
+
+vec3 srgbToLinear(vec3 color) {
+	return mix(
+		color / 12.92,
+		pow((color + 0.055) / 1.055, vec3(2.4)),
+		greaterThan(color, vec3(0.04045)));
+}
+
+vec3 grade(vec3 color) {
+	vec3 encoded = linearToSrgb(color);
+	float size = float(textureSize(lut, 0).x);
+	vec3 coords = clamp(
+		(encoded - settings.lutDomainMin) / (settings.lutDomainMax - settings.lutDomainMin),
+		0.0,
+		1.0);
+	// Centers of the first and last texels hold the ends of the domain.
+	vec3 graded = texture(lut, (coords * (size - 1.0) + 0.5) / size).rgb;
+	encoded = mix(encoded, clamp(graded, 0.0, 1.0), settings.lutStrength);
+	return srgbToLinear(encoded);
+}
+
+// Bilinear upscaling whose weights fall off with the luminance difference to
+// the nearest texel, so that edges stay sharp instead of blurring across.
+vec3 edgeAwareUpscale(vec2 uv) {
+	ivec2 size = textureSize(colorImage, 0);
+	vec2 position = uv * vec2(size) - 0.5;
+	ivec2 base = ivec2(floor(position));
+	vec2 f = fract(position);
+
+	vec3 nearest = texelFetch(colorImage, min(ivec2(uv * vec2(size)), size - 1), 0).rgb;
+	float nearestLuminance = luminance(nearest) / (1.0 + luminance(nearest));
+
+	vec3 sum = vec3(0.0);
+	float weightSum = 0.0;
+	for(int y = 0; y <= 1; y++) {
+		for(int x = 0; x <= 1; x++) {
+			vec3 color = texelFetch(colorImage, clamp(base + ivec2(x, y), ivec2(0), size - 1), 0).rgb;
+			float bilinear = (x == 0 ? 1.0 - f.x : f.x) * (y == 0 ? 1.0 - f.y : f.y);
+			// Luminance compressed like Reinhard, so bright pixels don't
+			// dominate the differences.
+			float difference = abs(luminance(color) / (1.0 + luminance(color)) - nearestLuminance);
+			float weight = bilinear * exp(-EDGE_SHARPNESS * difference);
+			sum += color * weight;
+			weightSum += weight;
+		}
+	}
+	// The nearest texel is one of the four, with a bilinear weight of at
+	// least a quarter.
+	return sum / max(weightSum, 1e-6);
+}
+
+// Color of the rendered image at this pixel, upscaled if it is smaller than
+// the swapchain image.
+vec3 sampleColor() {
+	if(textureSize(colorImage, 0) == ivec2(PushConstants.outputSize)) {
+		return texelFetch(colorImage, ivec2(gl_FragCoord.xy), 0).rgb;
+	}
+
+	vec2 uv = gl_FragCoord.xy / PushConstants.outputSize;
+	if(settings.upscaleFilter == UPSCALE_EDGE_AWARE) {
+		return edgeAwareUpscale(uv);
+	}
+	return texture(colorImage, uv).rgb;
+}
+
+// Draws the step size histogram in the bottom left corner, the smallest steps
+// on the left.
+vec3 drawStepHistogram(vec3 color) {
+	vec2 corner = vec2(OVERLAY_MARGIN, PushConstants.outputSize.y - OVERLAY_MARGIN - OVERLAY_SIZE.y);
+	vec2 position = (gl_FragCoord.xy - corner) / OVERLAY_SIZE;
+	if(any(lessThan(position, vec2(0.0))) || any(greaterThanEqual(position, vec2(1.0)))) {
+		return color;
+	}
+
+	uint maxCount = 1;
+	for(uint bin = 0; bin < STEP_BINS; bin++) {
+		maxCount = max(maxCount, stepBins[bin]);
+	}
+
+	// Square root scale, so rare step sizes stay visible.
+	uint bin = uint(position.x * float(STEP_BINS));
+	float height = sqrt(float(stepBins[bin]) / float(maxCount));
+	return 1.0 - position.y < height ? vec3(1.0, 0.8, 0.2) : color * 0.4;
+}
+
+void main() {
+	vec3 color = sampleColor();
+
+	if(PushConstants.debugView != DEBUG_OFF) {
+		// Debug colors are meant to be displayed as they are.
+		color = clamp(color, 0.0, 1.0);
+		if(PushConstants.debugView == DEBUG_STEP_HISTOGRAM) {
+			color = drawStepHistogram(color);
+		}
+		if(settings.outputEncoding == ENCODING_LINEAR) {
+			color = srgbToLinear(color);
+		}
+		f_color = vec4(color, 1.0);
+		return;
+	}
+
+	float exposure = exp2(settings.exposure);
+	if(settings.autoExposure != 0) {
+		exposure *= EXPOSURE_KEY / max(averageLuminance, 1e-4);
+	}
+	color *= exposure;
+
+	if(settings.toneMapper == TONE_MAPPER_ACES) {
+		color = aces(color);
+	} else if(settings.toneMapper == TONE_MAPPER_AGX) {
+		color = agx(color);
+	} else if(settings.toneMapper == TONE_MAPPER_REINHARD) {
+		color = reinhard(color);
+	}
+	color = clamp(color, 0.0, 1.0);
+
+	if(settings.lutStrength > 0.0) {
+		color = grade(color);
+	}
+
+	if(settings.outputEncoding == ENCODING_SRGB) {
+		color = linearToSrgb(color);
+	}
+
+	f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::PipelineStage;
+
+/// Most timestamps the command buffer of a swapchain image writes, the one
+/// starting it and one after each pass.
+const MAX_TIMESTAMPS: u32 = 16;
+
+/// Creates a pool of timestamp queries, `MAX_TIMESTAMPS` per swapchain image,
+/// or `None` if the queue family can't write timestamps.
+pub fn create_timestamp_pool(
+    device: &Arc<Device>,
+    queue_family_index: u32,
+    image_count: usize,
+) -> Option<Arc<QueryPool>> {
+    device.physical_device().queue_family_properties()[queue_family_index as usize]
+        .timestamp_valid_bits?;
+
+    Some(
+        QueryPool::new(
+            device.clone(),
+            QueryPoolCreateInfo {
+                query_count: MAX_TIMESTAMPS * image_count as u32,
+                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+            },
+        ).expect("Could not create timestamp query pool."),
+    )
+}
+
+/// Records the timestamps measuring the passes of a swapchain image's command
+/// buffer. Records nothing without a query pool.
+pub struct PassTimer<'a> {
+    query_pool: Option<&'a Arc<QueryPool>>,
+    first_query: u32,
+    passes: Vec<&'static str>,
+}
+
+impl<'a> PassTimer<'a> {
+    /// Records the timestamp starting the command buffer, which must come
+    /// before any other command.
+    pub fn start(
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        query_pool: Option<&'a Arc<QueryPool>>,
+        image_i: usize,
+    ) -> Self {
+        let first_query = MAX_TIMESTAMPS * image_i as u32;
+
+        // The queries of the image are only used by its own command buffer,
+        // which isn't executing while it is submitted again.
+        if let Some(query_pool) = query_pool {
+            unsafe {
+                builder
+                    .reset_query_pool(query_pool.clone(), first_query..first_query + MAX_TIMESTAMPS)
+                    .unwrap()
+                    .write_timestamp(query_pool.clone(), first_query, PipelineStage::TopOfPipe)
+                    .unwrap();
+            }
+        }
+
+        PassTimer {
+            query_pool,
+            first_query,
+            passes: Vec::new(),
+        }
+    }
+
+    /// Records the timestamp ending the named pass, made of the commands
+    /// recorded since the previous timestamp.
+    pub fn end_pass(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        name: &'static str,
+    ) {
+        let Some(query_pool) = self.query_pool else {
+            return;
+        };
+        assert!((self.passes.len() as u32) < MAX_TIMESTAMPS - 1, "Too many timed passes.");
+
+        self.passes.push(name);
+        unsafe {
+            builder
+                .write_timestamp(
+                    query_pool.clone(),
+                    self.first_query + self.passes.len() as u32,
+                    PipelineStage::BottomOfPipe,
+                )
+                .unwrap();
+        }
+    }
+
+    /// Returns the names of the timed passes, in order.
+    pub fn finish(self) -> Vec<&'static str> {
+        self.passes
+    }
+}
+
+/// GPU times of the passes of the last completed submission of a swapchain
+/// image's command buffer, in milliseconds, `None` if it hasn't been
+/// submitted yet. `passes` are the names the submitted command buffer was
+/// recorded with.
+pub fn read_pass_times(
+    query_pool: &Arc<QueryPool>,
+    image_i: usize,
+    passes: &[&'static str],
+) -> Option<Vec<(&'static str, f32)>> {
+    if passes.is_empty() {
+        return None;
+    }
+
+    let first_query = MAX_TIMESTAMPS * image_i as u32;
+    let mut timestamps = vec![0u64; passes.len() + 1];
+    let available = query_pool
+        .get_results(
+            first_query..first_query + timestamps.len() as u32,
+            &mut timestamps,
+            QueryResultFlags::empty(),
+        )
+        .expect("Could not read timestamp queries.");
+
+    // Ticks last `timestamp_period` nanoseconds.
+    let period = query_pool.device().physical_device().properties().timestamp_period;
+    available.then(|| {
+        passes
+            .iter()
+            .zip(timestamps.windows(2))
+            .map(|(name, pair)| (*name, pair[1].wrapping_sub(pair[0]) as f32 * period / 1.0e6))
+            .collect()
+    })
+}
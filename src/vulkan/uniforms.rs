@@ -0,0 +1,689 @@
+use std::sync::Arc;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::format::{Format, NumericFormat};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+use vulkano::swapchain::ColorSpace;
+
+use crate::atmosphere::Sun;
+use crate::camera::Camera;
+use crate::clouds::Clouds;
+use crate::post::PostEffect;
+use crate::scene::{self, Scene};
+use crate::settings::{
+    DebugView, ReconstructionFilter, RenderMode, RenderSettings, SamplePattern, ShadingModel,
+    ToneMapper, UpscaleFilter,
+};
+
+use super::shaders::{exposure_cs, fs, histogram_cs, post_fs, taa_fs, tonemap_fs};
+
+const LIGHT_DIRECTIONAL: u32 = 0;
+const LIGHT_POINT: u32 = 1;
+
+const SHAPE_SPHERE: u32 = 0;
+const SHAPE_BOX: u32 = 1;
+const SHAPE_TORUS: u32 = 2;
+const SHAPE_PLANE: u32 = 3;
+
+const VOLUME_CONSTANT: u32 = 0;
+const VOLUME_NOISE: u32 = 1;
+const VOLUME_TEXTURE: u32 = 2;
+
+const SAMPLE_GRID: u32 = 0;
+const SAMPLE_STRATIFIED: u32 = 1;
+const SAMPLE_ROTATED_GRID: u32 = 2;
+
+const FILTER_BOX: u32 = 0;
+const FILTER_GAUSSIAN: u32 = 1;
+const FILTER_MITCHELL: u32 = 2;
+
+const TONE_MAPPER_REINHARD: u32 = 0;
+const TONE_MAPPER_ACES: u32 = 1;
+const TONE_MAPPER_AGX: u32 = 2;
+const TONE_MAPPER_NONE: u32 = 3;
+
+const ENCODING_LINEAR: u32 = 0;
+const ENCODING_SRGB: u32 = 1;
+
+const UPSCALE_BILINEAR: u32 = 0;
+const UPSCALE_EDGE_AWARE: u32 = 1;
+
+const DEBUG_OFF: u32 = 0;
+const DEBUG_ITERATIONS: u32 = 1;
+const DEBUG_DEPTH: u32 = 2;
+const DEBUG_NORMALS: u32 = 3;
+const DEBUG_MATERIALS: u32 = 4;
+const DEBUG_STEP_HISTOGRAM: u32 = 5;
+
+const EFFECT_BLOOM: u32 = 0;
+const EFFECT_VIGNETTE: u32 = 1;
+const EFFECT_CHROMATIC_ABERRATION: u32 = 2;
+const EFFECT_FILM_GRAIN: u32 = 3;
+const EFFECT_SHARPEN: u32 = 4;
+
+fn light_data(light: &scene::Light) -> fs::Light {
+    match *light {
+        scene::Light::Directional { direction, color, intensity } => fs::Light {
+            position: direction,
+            kind: LIGHT_DIRECTIONAL,
+            color,
+            intensity,
+        },
+        scene::Light::Point { position, color, intensity } => fs::Light {
+            position,
+            kind: LIGHT_POINT,
+            color,
+            intensity,
+        },
+    }
+}
+
+/// `environment_map_levels` is the number of mip levels of the environment
+/// map, `0` when the procedural sky is used.
+fn scene_data(scene: &Scene, environment_map_levels: u32) -> fs::SceneData {
+    let mut lights = [fs::Light {
+        position: [0.0; 3],
+        kind: LIGHT_DIRECTIONAL,
+        color: [0.0; 3],
+        intensity: 0.0,
+    }; scene::MAX_LIGHTS];
+
+    for (data, light) in lights.iter_mut().zip(&scene.lights) {
+        *data = light_data(light);
+    }
+
+    let fog = scene.fog.clone().unwrap_or(scene::Fog {
+        density: 0.0,
+        falloff: 0.0,
+        height: 0.0,
+        albedo: [0.0; 3],
+        anisotropy: 0.0,
+    });
+    let clouds = scene.clouds.clone().unwrap_or(Clouds {
+        bottom: 0.0,
+        top: 1.0,
+        coverage: 0.0,
+        density: 0.0,
+        scale: 0.0,
+        detail_scale: 0.0,
+        wind: [0.0; 3],
+        forward_scattering: 0.0,
+        back_scattering: 0.0,
+    });
+
+    fs::SceneData {
+        lights,
+        ambient: scene.ambient,
+        lightCount: scene.lights.len().min(scene::MAX_LIGHTS) as u32,
+        skyZenith: scene.environment.zenith,
+        environmentMapLevels: environment_map_levels,
+        skyHorizon: scene.environment.horizon,
+        atmosphereTurbidity: scene.environment.atmosphere
+            .as_ref()
+            .map_or(0.0, |atmosphere| atmosphere.turbidity),
+        skyGround: scene.environment.ground,
+        atmosphere: scene.environment.atmosphere.is_some() as u32,
+        fogAlbedo: fog.albedo,
+        fogDensity: fog.density,
+        fogFalloff: fog.falloff,
+        fogHeight: fog.height,
+        fogAnisotropy: fog.anisotropy,
+        volumeCount: scene.volumes.len() as u32,
+        cloudBottom: clouds.bottom,
+        cloudTop: clouds.top,
+        cloudCoverage: clouds.coverage,
+        cloudDensity: clouds.density,
+        cloudWind: clouds.wind,
+        cloudScale: clouds.scale,
+        cloudDetailScale: clouds.detail_scale,
+        cloudForwardScattering: clouds.forward_scattering,
+        cloudBackScattering: clouds.back_scattering,
+        padding0: 0.0,
+    }
+}
+
+fn node_data(scene: &Scene, node: &scene::Node) -> fs::Node {
+    let (shape, params) = match node.shape {
+        scene::Shape::Sphere { radius } => (SHAPE_SPHERE, [radius, 0.0, 0.0, 0.0]),
+        scene::Shape::Box { size, rounding } => {
+            (SHAPE_BOX, [size[0], size[1], size[2], rounding])
+        }
+        scene::Shape::Torus { major_radius, minor_radius } => {
+            (SHAPE_TORUS, [major_radius, minor_radius, 0.0, 0.0])
+        }
+        scene::Shape::Plane { normal } => {
+            (SHAPE_PLANE, [normal[0], normal[1], normal[2], 0.0])
+        }
+    };
+
+    fs::Node {
+        position: node.position,
+        shape,
+        params,
+        repeat: node.repeat,
+        material: scene
+            .material_index(node.material.as_deref())
+            .expect("Scene nodes should reference existing materials.") as u32,
+    }
+}
+
+fn material_data(material: &scene::Material) -> fs::Material {
+    fs::Material {
+        albedo: material.albedo,
+        roughness: material.roughness,
+        emissive: material.emissive,
+        metallic: material.metallic,
+        specular: material.specular,
+        reflectivity: material.reflectivity,
+        transmission: material.transmission,
+        ior: material.ior,
+    }
+}
+
+fn volume_data(volume: &scene::Volume) -> fs::Volume {
+    let (density_type, noise_scale, noise_octaves) = match volume.field {
+        scene::VolumeDensity::Constant => (VOLUME_CONSTANT, 0.0, 0),
+        scene::VolumeDensity::Noise { scale, octaves } => (VOLUME_NOISE, scale, octaves),
+        scene::VolumeDensity::Texture { .. } => (VOLUME_TEXTURE, 0.0, 0),
+    };
+
+    fs::Volume {
+        position: volume.position,
+        densityType: density_type,
+        size: volume.size,
+        density: volume.density,
+        albedo: volume.albedo,
+        anisotropy: volume.anisotropy,
+        noiseScale: noise_scale,
+        noiseOctaves: noise_octaves,
+        padding0: 0.0,
+        padding1: 0.0,
+    }
+}
+
+pub fn create_scene_buffer(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    scene: &Scene,
+    environment_map_levels: u32,
+) -> Subbuffer<fs::SceneData> {
+    Buffer::from_data(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::UNIFORM_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        scene_data(scene, environment_map_levels),
+    ).expect("Could not create the scene uniform buffer.")
+}
+
+pub fn create_node_buffer(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    scene: &Scene,
+) -> Subbuffer<[fs::Node]> {
+    Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        scene.nodes.iter().map(|node| node_data(scene, node)),
+    ).expect("Could not create the scene node buffer.")
+}
+
+pub fn create_material_buffer(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    scene: &Scene,
+) -> Subbuffer<[fs::Material]> {
+    Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        scene.materials.iter().map(material_data),
+    ).expect("Could not create the material buffer.")
+}
+
+/// Storage buffers cannot be empty, scenes without volumes get a single unused
+/// entry.
+pub fn create_volume_buffer(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    scene: &Scene,
+) -> Subbuffer<[fs::Volume]> {
+    let placeholder = scene::Volume {
+        field: scene::VolumeDensity::Constant,
+        density: 0.0,
+        position: [0.0; 3],
+        size: [0.0; 3],
+        albedo: [0.0; 3],
+        anisotropy: 0.0,
+    };
+    let volumes = if scene.volumes.is_empty() {
+        std::slice::from_ref(&placeholder)
+    } else {
+        scene.volumes.as_slice()
+    };
+
+    Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        volumes.iter().map(volume_data),
+    ).expect("Could not create the volume buffer.")
+}
+
+pub fn settings_data(settings: &RenderSettings) -> fs::Settings {
+    fs::Settings {
+        shadows: settings.shadows as u32,
+        shadowSoftness: settings.shadow_softness,
+        ambientOcclusion: settings.ambient_occlusion as u32,
+        aoSamples: settings.ao_samples,
+        aoStepSize: settings.ao_step_size,
+        aoStrength: settings.ao_strength,
+        shadingModel: match settings.shading_model {
+            ShadingModel::BlinnPhong => 0,
+            ShadingModel::Pbr => 1,
+        },
+        maxBounces: settings.max_bounces,
+        renderMode: match settings.render_mode {
+            RenderMode::Direct => 0,
+            RenderMode::PathTraced => 1,
+        },
+        environmentRotation: settings.environment_rotation,
+        environmentIntensity: settings.environment_intensity,
+        volumetrics: settings.volumetrics as u32,
+        volumeSteps: settings.volume_steps,
+        clouds: settings.clouds as u32,
+        cloudSteps: settings.cloud_steps,
+        cloudLightSteps: settings.cloud_light_steps,
+        footprintLod: settings.footprint_lod as u32,
+        relaxation: if settings.over_relaxation { settings.relaxation_factor } else { 1.0 },
+        conePrepass: settings.cone_prepass as u32,
+        ssaaGridSize: settings.supersampling.grid_size,
+        ssaaPattern: match settings.supersampling.pattern {
+            SamplePattern::Grid => SAMPLE_GRID,
+            SamplePattern::Stratified => SAMPLE_STRATIFIED,
+            SamplePattern::RotatedGrid => SAMPLE_ROTATED_GRID,
+        },
+        ssaaFilter: match settings.supersampling.filter {
+            ReconstructionFilter::Box => FILTER_BOX,
+            ReconstructionFilter::Gaussian => FILTER_GAUSSIAN,
+            ReconstructionFilter::Mitchell => FILTER_MITCHELL,
+        },
+        marchStats: settings.march_stats as u32,
+    }
+}
+
+/// Creates one settings buffer per swapchain image, so a frame can be updated
+/// while the previous ones are still in flight.
+pub fn create_settings_buffers(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    settings: &RenderSettings,
+    count: usize,
+) -> Vec<Subbuffer<fs::Settings>> {
+    (0..count)
+        .map(|_| {
+            Buffer::from_data(
+                memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::UNIFORM_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                        MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+                settings_data(settings),
+            ).expect("Could not create a settings uniform buffer.")
+        })
+        .collect()
+}
+
+/// `jitter` is the subpixel offset of the primary rays, see `taa_jitter`.
+pub fn frame_data(
+    camera: &Camera,
+    sun: &Sun,
+    time: f32,
+    sample_count: u32,
+    frame_index: u32,
+    jitter: [f32; 2],
+) -> fs::FrameData {
+    fs::FrameData {
+        cameraPosition: camera.position,
+        sampleCount: sample_count,
+        cameraRight: camera.right(),
+        frameIndex: frame_index,
+        cameraUp: camera.up(),
+        padding0: 0.0,
+        cameraForward: camera.forward(),
+        padding1: 0.0,
+        sunDirection: sun.direction,
+        sunIntensity: sun.intensity,
+        sunColor: sun.color,
+        time,
+        jitter,
+    }
+}
+
+/// Number of jitter offsets the TAA cycles through.
+const TAA_JITTER_SAMPLES: u32 = 16;
+
+/// Subpixel offset of the primary rays in the given frame, from -0.5 to 0.5
+/// pixel, following the Halton sequence in bases 2 and 3.
+pub fn taa_jitter(frame_index: u32) -> [f32; 2] {
+    let halton = |mut index: u32, base: u32| {
+        let mut result = 0.0;
+        let mut fraction = 1.0;
+        while index > 0 {
+            fraction /= base as f32;
+            result += fraction * (index % base) as f32;
+            index /= base;
+        }
+        result
+    };
+
+    // The sequence starts at 1, as its first point is the corner.
+    let index = frame_index % TAA_JITTER_SAMPLES + 1;
+    [halton(index, 2) - 0.5, halton(index, 3) - 0.5]
+}
+
+/// Cameras of the current and previous frames the TAA reprojects between.
+/// `history_valid` is false when there is no history to reproject yet.
+pub fn taa_data(camera: &Camera, previous_camera: &Camera, history_valid: bool) -> taa_fs::TaaData {
+    taa_fs::TaaData {
+        cameraPosition: camera.position,
+        historyValid: history_valid as u32,
+        cameraRight: camera.right(),
+        padding0: 0.0,
+        cameraUp: camera.up(),
+        padding1: 0.0,
+        cameraForward: camera.forward(),
+        padding2: 0.0,
+        previousCameraPosition: previous_camera.position,
+        padding3: 0.0,
+        previousCameraRight: previous_camera.right(),
+        padding4: 0.0,
+        previousCameraUp: previous_camera.up(),
+        padding5: 0.0,
+        previousCameraForward: previous_camera.forward(),
+        padding6: 0.0,
+    }
+}
+
+/// Creates one frame data buffer per swapchain image, like the settings
+/// buffers.
+pub fn create_frame_buffers(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    camera: &Camera,
+    count: usize,
+) -> Vec<Subbuffer<fs::FrameData>> {
+    (0..count)
+        .map(|_| {
+            Buffer::from_data(
+                memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::UNIFORM_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                        MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+                frame_data(camera, &Sun::default(), 0.0, 0, 0, [0.0; 2]),
+            ).expect("Could not create a frame uniform buffer.")
+        })
+        .collect()
+}
+
+/// Creates one TAA uniform buffer per swapchain image, without history.
+pub fn create_taa_buffers(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    camera: &Camera,
+    count: usize,
+) -> Vec<Subbuffer<taa_fs::TaaData>> {
+    (0..count)
+        .map(|_| {
+            Buffer::from_data(
+                memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::UNIFORM_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                        MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+                taa_data(camera, camera, false),
+            ).expect("Could not create a TAA uniform buffer.")
+        })
+        .collect()
+}
+
+/// Encoding the tone mapping pass applies for the swapchain: none when the
+/// format encodes to sRGB itself or the color space is linear, the sRGB
+/// transfer function otherwise.
+pub fn output_encoding(format: Format, color_space: ColorSpace) -> u32 {
+    if format.numeric_format_color() == Some(NumericFormat::SRGB)
+        || color_space == ColorSpace::ExtendedSrgbLinear
+    {
+        ENCODING_LINEAR
+    } else {
+        ENCODING_SRGB
+    }
+}
+
+/// `delta_time` is the time since the previous frame in seconds, over which
+/// the auto exposure adapts.
+pub fn tone_map_data(
+    settings: &RenderSettings,
+    output_encoding: u32,
+    lut_domain: [[f32; 3]; 2],
+    delta_time: f32,
+) -> tonemap_fs::ToneMapSettings {
+    let lut_strength = settings.post
+        .iter()
+        .filter(|pass| pass.enabled)
+        .find_map(|pass| match pass.effect {
+            PostEffect::ColorGrading { strength, .. } => Some(strength),
+            _ => None,
+        });
+
+    tonemap_fs::ToneMapSettings {
+        toneMapper: match settings.tone_mapper {
+            ToneMapper::Reinhard => TONE_MAPPER_REINHARD,
+            ToneMapper::Aces => TONE_MAPPER_ACES,
+            ToneMapper::Agx => TONE_MAPPER_AGX,
+            ToneMapper::None => TONE_MAPPER_NONE,
+        },
+        autoExposure: settings.auto_exposure as u32,
+        exposure: settings.exposure,
+        adaptation: 1.0 - (-settings.exposure_adaptation * delta_time).exp(),
+        lutDomainMin: lut_domain[0],
+        lutStrength: lut_strength.unwrap_or(0.0),
+        lutDomainMax: lut_domain[1],
+        outputEncoding: output_encoding,
+        upscaleFilter: match settings.upscale_filter {
+            UpscaleFilter::Bilinear => UPSCALE_BILINEAR,
+            UpscaleFilter::EdgeAware => UPSCALE_EDGE_AWARE,
+        },
+    }
+}
+
+/// Debug view constant of the ray marching and tone mapping shaders.
+pub fn debug_view(debug_view: DebugView) -> u32 {
+    match debug_view {
+        DebugView::Off => DEBUG_OFF,
+        DebugView::Iterations => DEBUG_ITERATIONS,
+        DebugView::Depth => DEBUG_DEPTH,
+        DebugView::Normals => DEBUG_NORMALS,
+        DebugView::Materials => DEBUG_MATERIALS,
+        DebugView::StepHistogram => DEBUG_STEP_HISTOGRAM,
+    }
+}
+
+/// Push constants of the post-processing pass applying the effect.
+pub fn post_constants(effect: &PostEffect) -> post_fs::constants {
+    let (effect, strength) = match effect {
+        PostEffect::Bloom { intensity, .. } => (EFFECT_BLOOM, *intensity),
+        PostEffect::Vignette { intensity } => (EFFECT_VIGNETTE, *intensity),
+        PostEffect::ChromaticAberration { strength } => (EFFECT_CHROMATIC_ABERRATION, *strength),
+        PostEffect::FilmGrain { intensity } => (EFFECT_FILM_GRAIN, *intensity),
+        PostEffect::Sharpen { strength } => (EFFECT_SHARPEN, *strength),
+        PostEffect::ColorGrading { .. } => {
+            unreachable!("color grading is applied by the tone mapping pass")
+        }
+    };
+
+    post_fs::constants { effect, strength }
+}
+
+/// Creates one tone mapping settings buffer per swapchain image, like the
+/// settings buffers.
+pub fn create_tone_map_buffers(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    settings: &RenderSettings,
+    output_encoding: u32,
+    lut_domain: [[f32; 3]; 2],
+    count: usize,
+) -> Vec<Subbuffer<tonemap_fs::ToneMapSettings>> {
+    (0..count)
+        .map(|_| {
+            Buffer::from_data(
+                memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::UNIFORM_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                        MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+                tone_map_data(settings, output_encoding, lut_domain, 0.0),
+            ).expect("Could not create a tone mapping uniform buffer.")
+        })
+        .collect()
+}
+
+/// Luminance histogram of the auto exposure, starting empty.
+pub fn create_histogram_buffer(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+) -> Subbuffer<histogram_cs::Histogram> {
+    Buffer::from_data(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        histogram_cs::Histogram { bins: [0; 256] },
+    ).expect("Could not create the histogram buffer.")
+}
+
+/// Step size histogram of the step histogram debug view, cleared every frame.
+pub fn create_step_histogram_buffer(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+) -> Subbuffer<fs::StepHistogram> {
+    Buffer::from_data(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        fs::StepHistogram { stepBins: [0; 32] },
+    ).expect("Could not create the step histogram buffer.")
+}
+
+/// Creates one march statistics buffer per swapchain image, read back once
+/// the image's command buffer has completed.
+pub fn create_march_stats_buffers(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    count: usize,
+) -> Vec<Subbuffer<fs::MarchStats>> {
+    (0..count)
+        .map(|_| {
+            Buffer::from_data(
+                memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                        MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                    ..Default::default()
+                },
+                fs::MarchStats { rayCount: 0, iterations: 0, plainIterations: 0 },
+            ).expect("Could not create a march statistics buffer.")
+        })
+        .collect()
+}
+
+/// Adapted scene luminance of the auto exposure, `0.0` until measured.
+pub fn create_exposure_buffer(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+) -> Subbuffer<exposure_cs::Exposure> {
+    Buffer::from_data(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        exposure_cs::Exposure { averageLuminance: 0.0 },
+    ).expect("Could not create the exposure buffer.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taa_jitter_offsets_are_in_range_and_distinct() {
+        let offsets: Vec<[f32; 2]> = (0..TAA_JITTER_SAMPLES).map(taa_jitter).collect();
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(offset.iter().all(|value| (-0.5..0.5).contains(value)), "{offset:?}");
+            assert!(!offsets[..i].contains(offset), "{offset:?} repeats");
+        }
+        // The pixel's corner, the first point of the sequence, is skipped.
+        assert_ne!(offsets[0], [-0.5, -0.5]);
+        assert_eq!(taa_jitter(TAA_JITTER_SAMPLES), offsets[0]);
+    }
+}
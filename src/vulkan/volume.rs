@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::CopyBufferToImageInfo;
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+
+use half::f16;
+
+use crate::clouds::CLOUD_NOISE_SIZE;
+use crate::lut::Lut;
+use crate::volume::VolumeTexture;
+
+use super::command_buffers;
+
+/// Uploads the density grid of the scene's texture volume, or an empty
+/// placeholder when there is none.
+pub fn create_volume_image(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+    volume_texture: Option<&VolumeTexture>,
+) -> Arc<ImageView> {
+    let placeholder = VolumeTexture {
+        resolution: [1, 1, 1],
+        voxels: vec![0],
+    };
+    let volume_texture = volume_texture.unwrap_or(&placeholder);
+
+    create_image_3d(
+        memory_allocator,
+        command_buffer_allocator,
+        queue,
+        Format::R8_UNORM,
+        volume_texture.resolution,
+        &volume_texture.voxels,
+    )
+}
+
+/// Uploads the cloud noise texture, or an empty placeholder when the scene has
+/// no clouds.
+pub fn create_cloud_noise_image(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+    cloud_noise: Option<&[[u8; 4]]>,
+) -> Arc<ImageView> {
+    let (extent, texels) = match cloud_noise {
+        Some(texels) => ([CLOUD_NOISE_SIZE; 3], texels),
+        None => ([1, 1, 1], &[[0; 4]][..]),
+    };
+
+    create_image_3d(
+        memory_allocator,
+        command_buffer_allocator,
+        queue,
+        Format::R8G8B8A8_UNORM,
+        extent,
+        texels.as_flattened(),
+    )
+}
+
+/// Uploads the color grading LUT, or an empty placeholder when the scene has
+/// none.
+pub fn create_lut_image(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+    lut: Option<&Lut>,
+) -> Arc<ImageView> {
+    let (size, table) = match lut {
+        Some(lut) => (lut.size, &lut.table[..]),
+        None => (1, &[[0.0; 3]][..]),
+    };
+    let texels: Vec<u8> = table
+        .iter()
+        .flat_map(|&[r, g, b]| [r, g, b, 1.0])
+        .flat_map(|value| f16::from_f32(value).to_bits().to_ne_bytes())
+        .collect();
+
+    create_image_3d(
+        memory_allocator,
+        command_buffer_allocator,
+        queue,
+        Format::R16G16B16A16_SFLOAT,
+        [size; 3],
+        &texels,
+    )
+}
+
+fn create_image_3d(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+    format: Format,
+    extent: [u32; 3],
+    data: &[u8],
+) -> Arc<ImageView> {
+    let staging_buffer = Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST |
+                MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        data.iter().copied(),
+    ).expect("Could not create the 3D texture staging buffer.");
+
+    let image = Image::new(
+        memory_allocator.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim3d,
+            format,
+            extent,
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    ).expect("Could not create the 3D texture.");
+
+    command_buffers::submit_and_wait(command_buffer_allocator, queue, |builder| {
+        builder
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                staging_buffer,
+                image.clone(),
+            ))
+            .unwrap();
+    });
+
+    ImageView::new_default(image).unwrap()
+}
+
+pub fn create_volume_sampler(device: &Arc<Device>) -> Arc<Sampler> {
+    Sampler::new(
+        device.clone(),
+        SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            address_mode: [SamplerAddressMode::ClampToEdge; 3],
+            ..Default::default()
+        },
+    ).expect("Could not create the volume texture sampler.")
+}
+
+/// Trilinear sampler repeating the tileable cloud noise.
+pub fn create_cloud_noise_sampler(device: &Arc<Device>) -> Arc<Sampler> {
+    Sampler::new(
+        device.clone(),
+        SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            address_mode: [SamplerAddressMode::Repeat; 3],
+            ..Default::default()
+        },
+    ).expect("Could not create the cloud noise sampler.")
+}